@@ -1,43 +1,59 @@
+use crate::campaign::{Campaign, CampaignAssetLoader};
 use crate::combat_map::{CombatMap, CombatMapAssetLoader};
+use crate::dialog::{DialogAssetLoader, DialogScript};
 
 use bevy::utils::HashMap;
 use bevy::{asset::Handle, asset::LoadedFolder, prelude::*};
 
 use crate::characters::{
-    CharacterName, CharacterSkills, CharactersAssetLoader, DirectorCharacter, IconName, Initiative,
-    NoName, PlayerCharacter, PortraitAtlasId, SaveCharacters, Vitality,
+    CharacterName, CharactersAssetLoader, DirectorCharacter, IconName, Initiative, NoName,
+    PlayerCharacter, PortraitAtlasId, SaveCharacters, Vitality,
 };
+use crate::dice::{DiceSeed, SeededDice};
+use crate::scenes::{saved_story_points, NextScene};
 use crate::states::GameState;
+use crate::story::{self, NewGame, StoryPoints};
 
 #[derive(Resource)]
 pub struct PreloadAssets {
     pub(crate) fiction_font: Handle<Font>,
-    pub combat_map: Handle<CombatMap>,
+    pub campaign: Handle<Campaign>,
     pub characters: Handle<SaveCharacters>,
+    pub combat_maps: HashMap<String, Handle<CombatMap>>,
+    pub dialogs: HashMap<String, Handle<DialogScript>>,
 }
 
 #[derive(Resource)]
 pub struct CombatAsset {
     pub portrait_atlas: Handle<TextureAtlasLayout>,
     pub portrait_image: Handle<Image>,
+    pub portraits: HashMap<String, usize>,
     pub maps: HashMap<String, Handle<Image>>,
-    pub combat_map: Handle<CombatMap>,
+    pub campaign: Handle<Campaign>,
     pub characters: Handle<SaveCharacters>,
+    pub combat_maps: HashMap<String, Handle<CombatMap>>,
+    pub dialogs: HashMap<String, Handle<DialogScript>>,
 }
 
 pub struct AssetLoader;
 
 impl Plugin for AssetLoader {
     fn build(&self, app: &mut App) {
-        app.init_asset::<CombatMap>()
+        app.init_asset::<Campaign>()
+            .init_asset::<CombatMap>()
             .init_asset::<SaveCharacters>()
+            .init_asset::<DialogScript>()
+            .init_asset_loader::<CampaignAssetLoader>()
             .init_asset_loader::<CombatMapAssetLoader>()
             .init_asset_loader::<CharactersAssetLoader>()
+            .init_asset_loader::<DialogAssetLoader>()
             .add_systems(OnEnter(GameState::Splash), show_splash_screen)
             .add_systems(OnEnter(GameState::AssetsLoading), load_assets)
             .add_systems(
                 Update,
-                check_assets_loaded.run_if(in_state(GameState::AssetsLoading)),
+                (load_scene_assets, check_assets_loaded)
+                    .chain()
+                    .run_if(in_state(GameState::AssetsLoading)),
             )
             .add_systems(OnExit(GameState::AssetsLoading), setup_assets)
             .add_systems(OnEnter(GameState::AssetsSetup), to_game)
@@ -99,11 +115,37 @@ fn load_assets(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.insert_resource(MapsFolder(asset_server.load_folder("maps/bitmaps")));
     commands.insert_resource(PreloadAssets {
         fiction_font: asset_server.load("fonts/gnuolane-free.rg-regular.otf"),
-        combat_map: asset_server.load("maps/cell_blocks.map"),
+        campaign: asset_server.load("adventure.campaign"),
         characters: asset_server.load("characters.characters"),
+        combat_maps: HashMap::new(),
+        dialogs: HashMap::new(),
     });
 }
 
+/// Once the campaign is in, load the maps and dialogs its scenes use.
+fn load_scene_assets(
+    asset_server: Res<AssetServer>,
+    campaigns: Res<Assets<Campaign>>,
+    mut preloaded_assets: ResMut<PreloadAssets>,
+) {
+    if !preloaded_assets.combat_maps.is_empty() || !preloaded_assets.dialogs.is_empty() {
+        return;
+    }
+    let Some(campaign) = campaigns.get(&preloaded_assets.campaign) else {
+        return;
+    };
+    let combat_maps = campaign
+        .combat_maps()
+        .map(|path| (path.clone(), asset_server.load(path.clone())))
+        .collect();
+    let dialogs = campaign
+        .dialogs()
+        .map(|path| (path.clone(), asset_server.load(path.clone())))
+        .collect();
+    preloaded_assets.combat_maps = combat_maps;
+    preloaded_assets.dialogs = dialogs;
+}
+
 fn check_assets_loaded(
     server: Res<AssetServer>,
     preloaded_assets: Res<PreloadAssets>,
@@ -114,8 +156,17 @@ fn check_assets_loaded(
     mut timer: ResMut<SplashTimer>,
 ) {
     if server.is_loaded_with_dependencies(preloaded_assets.fiction_font.clone())
-        && server.is_loaded_with_dependencies(preloaded_assets.combat_map.clone())
+        && server.is_loaded_with_dependencies(preloaded_assets.campaign.clone())
         && server.is_loaded_with_dependencies(preloaded_assets.characters.clone())
+        && !(preloaded_assets.combat_maps.is_empty() && preloaded_assets.dialogs.is_empty())
+        && preloaded_assets
+            .combat_maps
+            .values()
+            .all(|combat_map| server.is_loaded_with_dependencies(combat_map))
+        && preloaded_assets
+            .dialogs
+            .values()
+            .all(|dialog| server.is_loaded_with_dependencies(dialog))
         && server.is_loaded_with_dependencies(&portrait_icons_folder.0)
         && server.is_loaded_with_dependencies(&maps_folder.0)
     {
@@ -135,6 +186,8 @@ fn setup_assets(
     mut texture_atlases: ResMut<Assets<TextureAtlasLayout>>,
     mut textures: ResMut<Assets<Image>>,
     save_chars: Res<Assets<SaveCharacters>>,
+    dice_seed: Res<DiceSeed>,
+    new_game: Res<NewGame>,
     mut characters: Query<(&IconName, &mut PortraitAtlasId)>,
 ) {
     let mut portrait_indexes = HashMap::new();
@@ -191,7 +244,30 @@ fn setup_assets(
         }
     }
 
+    let save_seed = save_chars
+        .get(preloaded_assets.characters.id())
+        .and_then(|e| e.seed);
+    let dice = match dice_seed.0.or(save_seed) {
+        Some(seed) => SeededDice::new(seed),
+        None => SeededDice::from_entropy(),
+    };
+    info!("Dice seed: {}", dice.seed());
+    commands.insert_resource(dice);
+
     if let Some(e) = save_chars.get(preloaded_assets.characters.id()) {
+        // Carry on with the story points of the last game, unless starting
+        // over.
+        let mut story_points = StoryPoints::from_save(e);
+        if new_game.0 {
+            match std::fs::remove_file(story::save_file()) {
+                Ok(()) => info!("new game, saved story points dropped"),
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+                Err(error) => warn!("could not drop saved story points: {}", error),
+            }
+        } else if let Some(saved) = saved_story_points() {
+            story_points.carry_over(&saved);
+        }
+        commands.insert_resource(story_points);
         debug!("Setup player characters");
         for (i, player_char) in e.player_characters.iter().enumerate() {
             debug!("pc got: {:?} -> {:?}", i, player_char);
@@ -208,11 +284,7 @@ fn setup_assets(
                         slug: player_char.tag.clone(),
                     },
                     portrait: PortraitAtlasId { index: *index },
-                    skills: CharacterSkills::new(
-                        player_char.get_agility(),
-                        player_char.get_alertness(),
-                        player_char.get_sneak(),
-                    ),
+                    skills: player_char.character_skills(),
                     vitality: Vitality {
                         value: player_char.vitality,
                     },
@@ -248,14 +320,28 @@ fn setup_assets(
     let combat_asset = CombatAsset {
         portrait_atlas,
         portrait_image,
+        portraits: portrait_indexes
+            .into_iter()
+            .map(|(name, index)| (name.to_string(), index))
+            .collect(),
         maps,
-        combat_map: preloaded_assets.combat_map.clone(),
+        campaign: preloaded_assets.campaign.clone(),
         characters: preloaded_assets.characters.clone(),
+        combat_maps: preloaded_assets.combat_maps.clone(),
+        dialogs: preloaded_assets.dialogs.clone(),
     };
     commands.insert_resource(combat_asset);
 }
 
-fn to_game(mut game_state: ResMut<NextState<GameState>>) {
+fn to_game(
+    preloaded_assets: Res<PreloadAssets>,
+    campaigns: Res<Assets<Campaign>>,
+    mut next_scene: ResMut<NextScene>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
     info!("to_game()");
-    game_state.set(GameState::Combat);
+    next_scene.0 = campaigns
+        .get(&preloaded_assets.campaign)
+        .map(|campaign| campaign.start.clone());
+    game_state.set(GameState::SceneChange);
 }
@@ -8,6 +8,24 @@ fn main() {
 
     let test_map = fs::read_to_string("assets/maps/cell_blocks.map").unwrap();
     let map: game_test::combat_map::CombatMap = ron::from_str(test_map.as_str()).unwrap();
+    map.validate().unwrap();
 
     println!("RON: {}", ron::to_string(&map).unwrap());
+
+    let test_campaign = fs::read_to_string("assets/adventure.campaign").unwrap();
+    let campaign: game_test::campaign::Campaign = ron::from_str(test_campaign.as_str()).unwrap();
+    campaign.validate().unwrap();
+    println!(
+        "{} scenes starting with {}",
+        campaign.scenes.len(),
+        campaign.start
+    );
+
+    for entry in fs::read_dir("assets/dialog").unwrap() {
+        let path = entry.unwrap().path();
+        let test_dialog = fs::read_to_string(&path).unwrap();
+        let dialog: game_test::dialog::DialogScript = ron::from_str(test_dialog.as_str()).unwrap();
+        dialog.validate().unwrap();
+        println!("{:?}: {} nodes", path, dialog.script.len());
+    }
 }
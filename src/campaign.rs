@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use bevy::utils::thiserror;
+use bevy::{
+    asset::{io::Reader, ron, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    reflect::TypePath,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::rules::Outcome;
+
+/// A step of the campaign. A scene without a next one ends the game.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub enum Scene {
+    Dialog {
+        /// Path of the `.talk.ron` script, e.g. `"dialog/the_cell.talk.ron"`.
+        script: String,
+        #[serde(default)]
+        next: Option<String>,
+    },
+    Combat {
+        /// Path of the `.map` to fight on, e.g. `"maps/cell_blocks.map"`.
+        map: String,
+        #[serde(default)]
+        victory: Option<String>,
+        #[serde(default)]
+        defeat: Option<String>,
+        /// Where to go after getting away, the defeat scene if unset.
+        #[serde(default)]
+        fled: Option<String>,
+    },
+}
+
+impl Scene {
+    /// The scene to go to once this one is over.
+    pub fn next(&self, outcome: Option<Outcome>) -> Option<&str> {
+        match self {
+            Scene::Dialog { next, .. } => next.as_deref(),
+            Scene::Combat {
+                victory,
+                defeat,
+                fled,
+                ..
+            } => match outcome {
+                Some(Outcome::Victory) => victory.as_deref(),
+                Some(Outcome::Defeat) => defeat.as_deref(),
+                Some(Outcome::Fled) => fled.as_ref().or(defeat.as_ref()).map(String::as_str),
+                None => None,
+            },
+        }
+    }
+
+    fn leads_to(&self) -> impl Iterator<Item = &String> {
+        let (first, second, third) = match self {
+            Scene::Dialog { next, .. } => (next, &None, &None),
+            Scene::Combat {
+                victory,
+                defeat,
+                fled,
+                ..
+            } => (victory, defeat, fled),
+        };
+        first.iter().chain(second.iter()).chain(third.iter())
+    }
+}
+
+/// The scenes of an adventure and the one it starts with.
+#[derive(Asset, TypePath, Debug, Deserialize, Serialize, Clone)]
+pub struct Campaign {
+    pub start: String,
+    pub scenes: HashMap<String, Scene>,
+}
+
+impl Campaign {
+    pub fn scene(&self, id: &str) -> Option<&Scene> {
+        self.scenes.get(id)
+    }
+
+    /// Paths of the dialog scripts used by the scenes.
+    pub fn dialogs(&self) -> impl Iterator<Item = &String> {
+        self.scenes.values().filter_map(|scene| match scene {
+            Scene::Dialog { script, .. } => Some(script),
+            Scene::Combat { .. } => None,
+        })
+    }
+
+    /// Paths of the combat maps used by the scenes.
+    pub fn combat_maps(&self) -> impl Iterator<Item = &String> {
+        self.scenes.values().filter_map(|scene| match scene {
+            Scene::Combat { map, .. } => Some(map),
+            Scene::Dialog { .. } => None,
+        })
+    }
+
+    /// Check that the campaign starts and goes on with scenes that exist.
+    pub fn validate(&self) -> Result<(), CampaignError> {
+        let leads_to = std::iter::once(&self.start)
+            .chain(self.scenes.values().flat_map(|scene| scene.leads_to()));
+        for id in leads_to {
+            if self.scene(id).is_none() {
+                return Err(CampaignError::UnknownScene(id.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, Error, PartialEq)]
+pub enum CampaignError {
+    #[error("No scene named {0}")]
+    UnknownScene(String),
+}
+
+#[derive(Default)]
+pub struct CampaignAssetLoader;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum CampaignAssetLoaderError {
+    /// An [IO](std::io) Error
+    #[error("Could not load asset: {0}")]
+    Io(#[from] std::io::Error),
+    /// A [RON](ron) Error
+    #[error("Could not parse RON: {0}")]
+    RonSpannedError(#[from] ron::error::SpannedError),
+    /// A campaign going to scenes that are missing
+    #[error("Invalid campaign: {0}")]
+    Campaign(#[from] CampaignError),
+}
+
+impl AssetLoader for CampaignAssetLoader {
+    type Asset = Campaign;
+    type Settings = ();
+    type Error = CampaignAssetLoaderError;
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let custom_asset = ron::de::from_bytes::<Campaign>(&bytes)?;
+            custom_asset.validate()?;
+            Ok(custom_asset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["campaign"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn campaign(scenes: &str) -> Campaign {
+        ron::from_str(&format!(r#"(start: "fight", scenes: {{ {} }})"#, scenes)).unwrap()
+    }
+
+    #[test]
+    fn a_combat_goes_on_by_its_outcome() {
+        let fought = campaign(
+            r#""fight": Combat(map: "maps/fight.map", victory: Some("won"), defeat: Some("lost")),
+               "won": Dialog(script: "won.talk.ron"),
+               "lost": Dialog(script: "lost.talk.ron"),"#,
+        );
+        let fight = fought.scene("fight").unwrap();
+        assert_eq!(fight.next(Some(Outcome::Victory)), Some("won"));
+        assert_eq!(fight.next(Some(Outcome::Defeat)), Some("lost"));
+        // Getting away counts as losing without a scene of its own.
+        assert_eq!(fight.next(Some(Outcome::Fled)), Some("lost"));
+        assert_eq!(fight.next(None), None);
+        assert_eq!(fought.validate(), Ok(()));
+
+        let fled = campaign(
+            r#""fight": Combat(map: "maps/fight.map", defeat: Some("lost"), fled: Some("away")),
+               "lost": Dialog(script: "lost.talk.ron"),
+               "away": Dialog(script: "away.talk.ron"),"#,
+        );
+        let fight = fled.scene("fight").unwrap();
+        assert_eq!(fight.next(Some(Outcome::Fled)), Some("away"));
+        assert_eq!(fight.next(Some(Outcome::Victory)), None);
+    }
+
+    #[test]
+    fn scenes_have_to_lead_to_scenes_that_exist() {
+        let missing_next =
+            campaign(r#""fight": Dialog(script: "fight.talk.ron", next: Some("nowhere")),"#);
+        assert_eq!(
+            missing_next.validate(),
+            Err(CampaignError::UnknownScene("nowhere".to_string()))
+        );
+
+        let missing_victory =
+            campaign(r#""fight": Combat(map: "maps/fight.map", victory: Some("nowhere")),"#);
+        assert_eq!(
+            missing_victory.validate(),
+            Err(CampaignError::UnknownScene("nowhere".to_string()))
+        );
+
+        let missing_start = campaign(r#""other": Dialog(script: "other.talk.ron"),"#);
+        assert_eq!(
+            missing_start.validate(),
+            Err(CampaignError::UnknownScene("fight".to_string()))
+        );
+    }
+}
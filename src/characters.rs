@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
 use bevy::prelude::*;
 use bevy::utils::thiserror;
 use bevy::{
@@ -5,10 +8,12 @@ use bevy::{
     reflect::TypePath,
     utils::BoxedFuture,
 };
-use rand::Rng;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::dice::Dice;
+use crate::rules;
+
 #[derive(Component, Debug, Default, Clone)]
 pub struct PortraitAtlasId {
     pub index: usize,
@@ -27,37 +32,43 @@ pub struct CharacterName {
     pub last: String,
 }
 
-#[derive(Debug)]
-pub struct Skill {
-    pub value: u8,
+/// Every skill of a character, with the ones they never learned at
+/// [`DEFAULT_SKILL`](rules::DEFAULT_SKILL).
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct CharacterSkills {
+    values: HashMap<SkillKey, u8>,
 }
 
-impl Default for Skill {
+impl Default for CharacterSkills {
     fn default() -> Self {
-        Self { value: 15 }
+        Self {
+            values: SkillKey::ALL
+                .iter()
+                .map(|key| (*key, rules::DEFAULT_SKILL))
+                .collect(),
+        }
     }
 }
 
-impl Skill {
-    pub fn new(value: u8) -> Self {
-        Self { value }
+impl CharacterSkills {
+    pub fn new(skills: &[SkillType]) -> Self {
+        let mut character_skills = Self::default();
+        for skill in skills.iter() {
+            character_skills.values.insert(skill.key(), skill.value());
+        }
+        character_skills
     }
-}
 
-#[derive(Component, Debug)]
-pub struct CharacterSkills {
-    pub agility: Skill,
-    pub alertness: Skill,
-    pub sneak: Skill,
-}
+    pub fn get(&self, key: SkillKey) -> u8 {
+        self.values
+            .get(&key)
+            .copied()
+            .unwrap_or(rules::DEFAULT_SKILL)
+    }
 
-impl CharacterSkills {
-    pub fn new(agility: u8, alertness: u8, sneak: u8) -> Self {
-        Self {
-            agility: Skill::new(agility),
-            alertness: Skill::new(alertness),
-            sneak: Skill::new(sneak),
-        }
+    /// All the skills, in the order of [`SkillKey::ALL`].
+    pub fn iter(&self) -> impl Iterator<Item = (SkillKey, u8)> + '_ {
+        SkillKey::ALL.iter().map(|key| (*key, self.get(*key)))
     }
 }
 
@@ -66,6 +77,13 @@ pub struct Vitality {
     pub value: u8,
 }
 
+/// The weapon a character has in hand.
+#[derive(Component, Debug, Default)]
+pub struct EquippedWeapon {
+    pub index: usize,
+    pub name: String,
+}
+
 #[derive(Component, Debug)]
 pub struct Initiative {
     pub value: u8,
@@ -115,36 +133,86 @@ pub struct PlayerName {
     pub alias: String,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
-pub enum SkillType {
-    Agility(u8),
-    Alertness(u8),
-    Charm(u8),
-    Contacts(u8),
-    Credit(u8),
-    Electronics(u8),
-    Endurance(u8),
-    Engineering(u8),
-    Entertainment(u8),
-    Humanities(u8),
-    Investigation(u8),
-    Languages(u8),
-    Machinery(u8),
-    Medicine(u8),
-    Melee(u8),
-    Prestidigitation(u8),
-    RangedCombat(u8),
-    RedTape(u8),
-    Science(u8),
-    Search(u8),
-    Security(u8),
-    Sneak(u8),
-    Status(u8),
-    Strength(u8),
-    Subterfuge(u8),
-    Survival(u8),
-    Vehicles(u8),
-    Willpower(u8),
+/// Declares [`SkillType`] and [`SkillKey`] from the one list of skills, so
+/// the two never drift apart.
+macro_rules! skills {
+    ($($skill:ident),* $(,)?) => {
+        #[derive(Debug, Deserialize, Serialize, Clone)]
+        pub enum SkillType {
+            $($skill(u8),)*
+        }
+
+        impl SkillType {
+            pub fn key(&self) -> SkillKey {
+                match self {
+                    $(SkillType::$skill(_) => SkillKey::$skill,)*
+                }
+            }
+
+            pub fn value(&self) -> u8 {
+                match self {
+                    $(SkillType::$skill(value))|* => *value,
+                }
+            }
+        }
+
+        /// Which skill, without a value, for looking skills up.
+        #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum SkillKey {
+            $($skill,)*
+        }
+
+        impl SkillKey {
+            pub const ALL: &'static [SkillKey] = &[$(SkillKey::$skill,)*];
+        }
+    };
+}
+
+skills!(
+    Agility,
+    Alertness,
+    Charm,
+    Contacts,
+    Credit,
+    Electronics,
+    Endurance,
+    Engineering,
+    Entertainment,
+    Humanities,
+    Investigation,
+    Languages,
+    Machinery,
+    Medicine,
+    Melee,
+    Prestidigitation,
+    RangedCombat,
+    RedTape,
+    Science,
+    Search,
+    Security,
+    Sneak,
+    Status,
+    Strength,
+    Subterfuge,
+    Survival,
+    Vehicles,
+    Willpower,
+);
+
+#[derive(Debug, Error, PartialEq)]
+#[error("Unknown skill: {0}")]
+pub struct UnknownSkill(pub String);
+
+impl FromStr for SkillKey {
+    type Err = UnknownSkill;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        SkillKey::ALL
+            .iter()
+            .copied()
+            .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| UnknownSkill(name.to_string()))
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -165,7 +233,7 @@ pub enum AbilityType {
     MadInventor,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
 pub enum ComplicationType {
     Overconfident,
     CodeOfHonour,
@@ -177,7 +245,7 @@ pub enum ComplicationType {
     Sleepy,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
 pub enum LanguageLevel {
     Native,
     Fluent,
@@ -190,11 +258,11 @@ pub enum LanguageLevel {
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct LanguageType {
-    name: String,
-    level: LanguageLevel,
+    pub name: String,
+    pub level: LanguageLevel,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
 pub enum PlotHook {
     MediaDarling,
     LookingForACase,
@@ -204,7 +272,7 @@ pub enum PlotHook {
     ArchEnemy,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
 pub enum GearKitType {
     BeachWear,
     Bicycle,
@@ -237,6 +305,42 @@ pub enum GearKitType {
     WalkieTalkie,
 }
 
+impl GearKitType {
+    /// What the kit gives to fight with, if anything.
+    pub fn weapon(&self) -> Option<WeaponProfile> {
+        match self {
+            GearKitType::Handgun => Some(WeaponProfile {
+                name: "Handgun",
+                skill: SkillKey::RangedCombat,
+                damage: 3,
+                tags: vec![AttackTag::Reload(8)],
+            }),
+            GearKitType::PocketHandgun => Some(WeaponProfile {
+                name: "Pocket handgun",
+                skill: SkillKey::RangedCombat,
+                damage: 2,
+                tags: vec![AttackTag::ShortRange, AttackTag::Reload(6)],
+            }),
+            GearKitType::HuntingRifle => Some(WeaponProfile {
+                name: "Hunting rifle",
+                skill: SkillKey::RangedCombat,
+                damage: 4,
+                tags: vec![AttackTag::Reload(5)],
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// A weapon as it comes with a gear kit, before it is in anyone's hands.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeaponProfile {
+    pub name: &'static str,
+    pub skill: SkillKey,
+    pub damage: u8,
+    pub tags: Vec<AttackTag>,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct GearKit {
     kit_type: GearKitType,
@@ -244,6 +348,18 @@ pub struct GearKit {
     scene: bool,
 }
 
+impl GearKit {
+    pub fn kit_type(&self) -> GearKitType {
+        self.kit_type
+    }
+
+    /// Whether the kit is the character's signature gear, which a story
+    /// point can bring into play.
+    pub fn is_signature(&self) -> bool {
+        self.signature
+    }
+}
+
 #[derive(Asset, TypePath, Debug, Deserialize, Serialize, Clone)]
 pub struct SavePlayerCharacter {
     pub tag: String,
@@ -256,65 +372,58 @@ pub struct SavePlayerCharacter {
     pub languages: Vec<LanguageType>,
     pub plot_hooks: Vec<PlotHook>,
     pub gear_kits: Vec<GearKit>,
+    /// Story points left over from earlier scenes.
+    #[serde(default)]
+    pub story_points: u8,
 }
 
 impl SavePlayerCharacter {
-    pub fn get_agility(&self) -> u8 {
-        for skill in self.skills.iter() {
-            match skill {
-                SkillType::Agility(value) => {
-                    return value.clone();
-                }
-                _ => {}
-            }
-        }
-        15
-    }
-    pub fn get_alertness(&self) -> u8 {
-        for skill in self.skills.iter() {
-            match skill {
-                SkillType::Alertness(value) => {
-                    return value.clone();
-                }
-                _ => {}
-            }
-        }
-        15
+    pub fn character_skills(&self) -> CharacterSkills {
+        CharacterSkills::new(&self.skills)
     }
-    pub fn get_sneak(&self) -> u8 {
-        for skill in self.skills.iter() {
-            match skill {
-                SkillType::Sneak(value) => {
-                    return value.clone();
-                }
-                _ => {}
-            }
-        }
-        15
+
+    pub fn get_skill(&self, key: SkillKey) -> u8 {
+        self.skills
+            .iter()
+            .rev()
+            .find(|skill| skill.key() == key)
+            .map(|skill| skill.value())
+            .unwrap_or(rules::DEFAULT_SKILL)
     }
 
-    pub fn initiative(&self) -> u8 {
-        let mut rng = rand::thread_rng();
-        let roll = rng.gen_range(1..=100);
-        let tens = roll / 10u8;
-        let once = roll % 10u8;
-        match self.get_alertness() {
-            x if x <= roll => {
-                if tens == once {
-                    tens + once + 10
-                } else {
-                    tens + once
+    /// Bare hands first, then the weapons from the gear kits.
+    pub fn attacks(&self) -> Vec<Attack> {
+        let weapons = self
+            .gear_kits
+            .iter()
+            .filter_map(|kit| Some((kit.kit_type().weapon()?, kit.is_signature())))
+            .map(|(weapon, signature)| {
+                let mut tags = weapon.tags;
+                if signature {
+                    tags.push(AttackTag::Signature);
                 }
-            }
-            x if x > roll => {
-                if tens == once {
-                    0
-                } else {
-                    once
+                Attack {
+                    name: weapon.name.to_string(),
+                    skill: self.get_skill(weapon.skill),
+                    damage: weapon.damage,
+                    tags,
                 }
-            }
-            _ => 0,
-        }
+            });
+        std::iter::once(Attack::unarmed(self.get_skill(SkillKey::Melee)))
+            .chain(weapons)
+            .collect()
+    }
+
+    /// How well the character speaks a language, `None` for not at all.
+    pub fn language_level(&self, language: &str) -> Option<LanguageLevel> {
+        self.languages
+            .iter()
+            .find(|known| known.name.eq_ignore_ascii_case(language))
+            .map(|known| known.level)
+    }
+
+    pub fn initiative(&self, dice: &mut impl Dice) -> u8 {
+        rules::initiative(self.get_skill(SkillKey::Alertness), dice.d100())
     }
 }
 
@@ -326,19 +435,50 @@ pub enum DCTag {
     MultipleAttacks(u8),
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub enum AttackTag {
+    /// Hand to hand, only reaching someone in the same zone.
+    Melee,
+    /// Bare hands, as improved by abilities like judo.
+    Unarmed,
+    /// Does not reach further than the next zone.
     ShortRange,
     Reload(u8),
+    /// A hit leaves the target paralysed for a couple of rounds.
     Paralytic,
+    /// A hit knocks the target over.
+    Knockback,
+    /// A hit sweeps the target off their feet.
+    Trip,
+    /// Signature gear, a story point gives a bonus on the attack check.
+    Signature,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Attack {
-    name: String,
-    skill: u8,
-    damage: u8,
-    tags: Vec<AttackTag>,
+    pub name: String,
+    pub skill: u8,
+    pub damage: u8,
+    pub tags: Vec<AttackTag>,
+}
+
+impl Attack {
+    pub fn unarmed(skill: u8) -> Self {
+        Self {
+            name: "Unarmed".to_string(),
+            skill,
+            damage: 2,
+            tags: vec![AttackTag::Melee, AttackTag::Unarmed],
+        }
+    }
+
+    /// How many shots the attack holds between reloads, if it needs any.
+    pub fn capacity(&self) -> Option<u8> {
+        self.tags.iter().find_map(|tag| match tag {
+            AttackTag::Reload(shots) => Some(*shots),
+            _ => None,
+        })
+    }
 }
 
 #[derive(Asset, TypePath, Debug, Deserialize, Serialize, Clone)]
@@ -350,10 +490,18 @@ pub struct SaveDirectorCharacter {
     pub attacks: Vec<Attack>,
 }
 
-#[derive(Asset, TypePath, Debug, Deserialize, Serialize)]
+impl SaveDirectorCharacter {
+    pub fn is_mook(&self) -> bool {
+        self.tags.iter().any(|tag| matches!(tag, DCTag::Mook))
+    }
+}
+
+#[derive(Asset, TypePath, Debug, Deserialize, Serialize, Clone)]
 pub struct SaveCharacters {
     pub player_characters: Vec<SavePlayerCharacter>,
     pub director_characters: Vec<SaveDirectorCharacter>,
+    #[serde(default)]
+    pub seed: Option<u64>,
 }
 
 #[derive(Debug, Clone)]
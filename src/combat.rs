@@ -1,18 +1,30 @@
+use std::collections::VecDeque;
 use std::vec::Vec;
 
 use bevy::prelude::*;
 use bevy::utils::HashMap;
 use bevy::window::PrimaryWindow;
-use rand::Rng;
 
 use crate::asset_loader::CombatAsset;
+use crate::campaign::Scene;
 use crate::characters::{
-    CharacterName, CharacterSkills, CharacterType, DirectorCharacter, IconName, Initiative, NoName,
+    CharacterName, CharacterSkills, CharacterType, EquippedWeapon, Initiative, NoName,
     PortraitAtlasId, SaveCharacters, Vitality,
 };
-use crate::combat_map::CombatMap;
+use crate::combat_map::{CombatMap, Objective};
+use crate::dice::SeededDice;
+use crate::rules::checks::CheckResult;
+use crate::rules::director::{behaviour_for, DirectorFlips};
+use crate::rules::effects::StatusKind;
+use crate::rules::flips::{Flipper, StorySpend, SIGNATURE_GEAR_BONUS};
+use crate::rules::{
+    Action, CombatEvent, CombatResult, CombatState, Combatant, CombatantId, ObjectiveStatus,
+    Outcome, RulesError, Side,
+};
+use crate::scenes::{CurrentScene, NextScene};
 use crate::schedule::CombatUpdateSets;
 use crate::states::GameState;
+use crate::story::{StoryPoints, COMPLICATION_AWARD};
 use crate::utils::despawn_screen;
 use crate::MainCamera;
 
@@ -21,6 +33,12 @@ pub struct Combat;
 impl Plugin for Combat {
     fn build(&self, app: &mut App) {
         app.init_resource::<MyWorldCoords>()
+            .init_resource::<Encounter>()
+            .init_resource::<CombatLog>()
+            .init_resource::<DirectorTurn>()
+            .init_resource::<LastCombat>()
+            .init_resource::<CheckOffer>()
+            .add_event::<RulesEvent>()
             .add_systems(OnEnter(GameState::Combat), combat_setup)
             .add_systems(
                 OnExit(GameState::Combat),
@@ -35,30 +53,56 @@ impl Plugin for Combat {
             )
             .add_systems(
                 Update,
-                (my_cursor_system, button_interaction_system)
+                (
+                    my_cursor_system,
+                    button_interaction_system,
+                    end_turn,
+                    answer_check_offer,
+                    get_up,
+                    switch_weapon,
+                    reload,
+                    director_turn,
+                    show_attack_targets,
+                    choose_attack_target,
+                    show_move_zones,
+                    show_move_cancel,
+                    choose_move_zone,
+                    clear_move_zones,
+                    cancel_action,
+                )
                     .run_if(in_state(GameState::CombatTurns))
                     .in_set(CombatUpdateSets::UserInput),
             )
             .add_systems(
                 Update,
                 (
+                    apply_rules_events,
+                    sync_story_points,
+                    log_rules_events,
+                    show_combat_log,
                     show_initiative,
+                    show_round,
+                    show_objectives,
+                    show_weapon,
+                    show_check_offer,
                     show_button_state,
                     draw_icons_in_zone,
                     render_zones,
+                    check_combat_end,
                     bevy::window::close_on_esc,
                 )
                     .run_if(in_state(GameState::CombatTurns))
                     .in_set(CombatUpdateSets::EntityUpdates),
             )
+            .add_systems(OnEnter(GameState::CombatEnded), show_combat_result)
             .add_systems(
                 Update,
-                (draw_icons_in_zone, bevy::window::close_on_esc)
+                (draw_icons_in_zone, leave_combat, bevy::window::close_on_esc)
                     .run_if(in_state(GameState::CombatEnded)),
             )
             .add_systems(
                 OnExit(GameState::CombatEnded),
-                despawn_screen::<OnCombatScreen>,
+                (clear_combat_tokens, despawn_screen::<OnCombatScreen>),
             );
     }
 }
@@ -66,6 +110,11 @@ impl Plugin for Combat {
 #[derive(Component)]
 struct OnCombatScreen;
 
+/// Marks a player character put on the combat map. The character outlives the
+/// combat, so only what the combat added is taken off again when it ends.
+#[derive(Component)]
+struct CombatToken;
+
 #[derive(Component)]
 struct InitiativeSprite;
 
@@ -87,6 +136,9 @@ struct SwitchButton;
 #[derive(Component)]
 struct GetUpButton;
 
+#[derive(Component)]
+struct ReloadButton;
+
 #[derive(Component)]
 struct EndTurnButton;
 
@@ -108,9 +160,206 @@ struct AdjacentZone;
 #[derive(Component)]
 struct HooverZone;
 
+#[derive(Component)]
+struct RoundText;
+
+#[derive(Component)]
+struct ActionPanel;
+
+#[derive(Component)]
+struct TargetMenu;
+
+#[derive(Component)]
+struct TargetButton(CombatantId);
+
+#[derive(Component)]
+struct CancelButton;
+
+#[derive(Component)]
+struct CombatLogText;
+
+#[derive(Component)]
+struct ContinueButton;
+
+#[derive(Component)]
+struct ObjectiveText;
+
+#[derive(Component)]
+struct WeaponText;
+
+#[derive(Component)]
+struct CheckPrompt;
+
+#[derive(Component)]
+struct SpendButton(CheckChoice);
+
+#[derive(Component)]
+struct KeepButton;
+
+#[derive(Component)]
+struct SquadBadge;
+
+/// Marks a status effect over the token it is a child of.
+#[derive(Component)]
+struct StatusIcon(StatusKind);
+
+/// How the last combat went, kept for the scenes that follow it.
+#[derive(Resource, Default, Debug)]
+pub struct LastCombat(pub Option<CombatResult>);
+
+/// What the player can do about a failed check of a player character.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CheckChoice {
+    /// Flip it with a flip from the pool.
+    Flip,
+    /// Spend a story point of the roller on it.
+    Story(StorySpend),
+}
+
+/// An action held back while the player decides whether to spend a flip or
+/// a story point on a check rolled for it, with the dice as they were before
+/// it was rolled. No action stands for the opening of the combat.
+#[derive(Debug)]
+struct PendingCheck {
+    action: Option<Action>,
+    dice: SeededDice,
+    roller: CombatantId,
+    check: CheckResult,
+    choices: Vec<CheckChoice>,
+}
+
+#[derive(Resource, Default, Debug)]
+struct CheckOffer(Option<PendingCheck>);
+
+/// Spends a flip or a story point on a player character's check as answered
+/// in the check prompt, offering only the first check of an action that
+/// failed. Director characters decide for themselves.
+#[derive(Default)]
+struct PromptedSpend {
+    answer: Option<CheckChoice>,
+    offered: Option<(CombatantId, CheckResult, Vec<CheckChoice>)>,
+    /// Whether the offered check is settled, leaving later checks alone.
+    settled: bool,
+}
+
+impl PromptedSpend {
+    /// Whether the check is the one on offer, taking it if nothing is yet.
+    fn is_offered(&mut self, roller: CombatantId, check: &CheckResult) -> bool {
+        if self.settled || check.is_success() {
+            return false;
+        }
+        match &self.offered {
+            Some((offered_roller, offered_check, _)) => {
+                *offered_roller == roller && offered_check == check
+            }
+            None => {
+                self.offered = Some((roller, *check, Vec::new()));
+                true
+            }
+        }
+    }
+
+    fn offer(&mut self, choice: CheckChoice) {
+        if let Some((_, _, choices)) = &mut self.offered {
+            choices.push(choice);
+        }
+    }
+}
+
+fn is_player(state: &CombatState, roller: CombatantId) -> bool {
+    state.combatant(roller).map(|combatant| combatant.side) == Some(Side::Players)
+}
+
+impl Flipper for PromptedSpend {
+    fn flip(&mut self, state: &CombatState, roller: CombatantId, check: &CheckResult) -> bool {
+        if !is_player(state, roller) {
+            return DirectorFlips.flip(state, roller, check);
+        }
+        if !self.is_offered(roller, check) {
+            return false;
+        }
+        if check.flipped().is_success() {
+            self.offer(CheckChoice::Flip);
+        }
+        let flip = self.answer == Some(CheckChoice::Flip);
+        self.settled |= flip;
+        flip
+    }
+
+    fn spend_story_point(
+        &mut self,
+        state: &CombatState,
+        roller: CombatantId,
+        check: &CheckResult,
+        spends: &[StorySpend],
+    ) -> Option<StorySpend> {
+        if !is_player(state, roller) || !self.is_offered(roller, check) {
+            return None;
+        }
+        self.settled = true;
+        for spend in spends {
+            // A flip is only worth a story point if it turns the check around.
+            if *spend != StorySpend::Flip || check.flipped().is_success() {
+                self.offer(CheckChoice::Story(*spend));
+            }
+        }
+        match self.answer {
+            Some(CheckChoice::Story(spend)) => Some(spend),
+            _ => None,
+        }
+    }
+}
+
+/// The most recent things that happened in combat, as shown to the player.
+#[derive(Resource, Default, Debug)]
+struct CombatLog(VecDeque<String>);
+
+const COMBAT_LOG_LINES: usize = 8;
+
+impl CombatLog {
+    /// Add a line, dropping the oldest once there are more than are shown.
+    fn push(&mut self, line: String) {
+        self.0.push_back(line);
+        while self.0.len() > COMBAT_LOG_LINES {
+            self.0.pop_front();
+        }
+    }
+}
+
 #[derive(Resource, Default, Debug)]
 struct MyWorldCoords(Vec2);
 
+/// The rules state of the running encounter.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub struct Encounter(pub CombatState);
+
+/// Pacing and bookkeeping for the director character whose turn it is.
+#[derive(Resource, Debug)]
+struct DirectorTurn {
+    timer: Timer,
+}
+
+impl Default for DirectorTurn {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(0.6, TimerMode::Repeating),
+        }
+    }
+}
+
+/// Something that happened in the rules, for the systems that show it.
+#[derive(Event, Debug, Deref)]
+pub struct RulesEvent(pub CombatEvent);
+
+pub fn combatant_id(entity: Entity) -> CombatantId {
+    CombatantId(entity.to_bits())
+}
+
+pub fn combatant_entity(id: CombatantId) -> Entity {
+    Entity::from_bits(id.0)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn combat_setup(
     mut commands: Commands,
     combat_asset: Res<CombatAsset>,
@@ -120,18 +369,37 @@ fn combat_setup(
     mut game_state: ResMut<NextState<GameState>>,
     saved_characters: Res<Assets<SaveCharacters>>,
     combat_maps: Res<Assets<CombatMap>>,
+    current_scene: Res<CurrentScene>,
+    mut dice: ResMut<SeededDice>,
+    story_points: Res<StoryPoints>,
+    asset_server: Res<AssetServer>,
 ) {
     info!("combat_setup...");
+    commands.insert_resource(CombatLog::default());
     let mut window = windows.single_mut();
     window.resolution.set(2048.0, 1024.0);
 
     // Draw map
-    if let Some(combat_map) = combat_maps.get(combat_asset.combat_map.clone()) {
+    let combat_map = match &current_scene.scene {
+        Scene::Combat { map, .. } => combat_asset
+            .combat_maps
+            .get(map)
+            .and_then(|handle| combat_maps.get(handle)),
+        Scene::Dialog { .. } => None,
+    };
+    if let Some(combat_map) = combat_map {
         debug!("combat_map: {:?}", combat_map);
         if let Some(saved_chars) = saved_characters.get(combat_asset.characters.clone()) {
             setup_combat_map(&mut commands, combat_map, &combat_asset);
+            let mut combatants = Vec::new();
 
-            for in_scene in combat_map.start_positions.iter() {
+            let start_groups = combat_map.start_groups(|start| {
+                matches!(
+                    saved_chars.get_char_for_tag(start.entity_tag.clone()),
+                    Some(CharacterType::DirectorCharacter { char }) if char.is_mook()
+                )
+            });
+            for (in_scene, count) in start_groups {
                 if let Some(char_type) = saved_chars.get_char_for_tag(in_scene.entity_tag.clone()) {
                     match char_type {
                         CharacterType::PlayerCharacter { char } => {
@@ -141,9 +409,32 @@ fn combat_setup(
                                     &in_scene.entity_tag,
                                 )
                             {
-                                let initiative = char.initiative();
+                                let initiative = char.initiative(&mut *dice);
+                                combatants.push(
+                                    Combatant::new(
+                                        combatant_id(entity),
+                                        Side::Players,
+                                        initiative,
+                                        char.vitality,
+                                        &in_scene.zone_tag,
+                                    )
+                                    .with_slug(&in_scene.entity_tag)
+                                    .with_attacks(char.attacks())
+                                    .with_skills(char.character_skills())
+                                    .with_abilities(char.abilities.clone())
+                                    .with_complications(char.complications.clone())
+                                    .with_story_points(story_points.get(&char.tag)),
+                                );
                                 let character_initiative = Initiative::new(initiative);
                                 commands.entity(entity).insert(character_initiative);
+                                commands.entity(entity).insert(EquippedWeapon {
+                                    index: 0,
+                                    name: char
+                                        .attacks()
+                                        .first()
+                                        .map(|attack| attack.name.clone())
+                                        .unwrap_or_default(),
+                                });
                                 commands
                                     .entity(entity)
                                     .insert(InZone::new(in_scene.zone_tag.as_str()));
@@ -161,38 +452,61 @@ fn combat_setup(
                                 &director_characters,
                                 &in_scene.entity_tag,
                             ) {
-                                commands.spawn((
-                                    NoName {
-                                        slug: in_scene.entity_tag.clone(),
-                                        alias: in_scene.entity_tag.clone(),
-                                        generic: in_scene.entity_tag.clone(),
-                                    },
-                                    Initiative {
-                                        value: char.initiative,
-                                    },
-                                    Vitality {
-                                        value: char.vitality,
-                                    },
-                                    SpriteSheetBundle {
-                                        transform: Transform {
-                                            translation: Vec3::new(100., -400., 3.),
-                                            ..default()
+                                let alias = if count > 1 {
+                                    format!("{} squad", in_scene.entity_tag)
+                                } else {
+                                    in_scene.entity_tag.clone()
+                                };
+                                let entity = commands
+                                    .spawn((
+                                        NoName {
+                                            slug: in_scene.entity_tag.clone(),
+                                            alias,
+                                            generic: in_scene.entity_tag.clone(),
+                                        },
+                                        Initiative {
+                                            value: char.initiative,
                                         },
-                                        atlas: TextureAtlas {
-                                            layout: combat_asset.portrait_atlas.clone(),
-                                            index: portait_id.index,
+                                        Vitality {
+                                            value: char.vitality,
+                                        },
+                                        SpriteSheetBundle {
+                                            transform: Transform {
+                                                translation: Vec3::new(100., -400., 3.),
+                                                ..default()
+                                            },
+                                            atlas: TextureAtlas {
+                                                layout: combat_asset.portrait_atlas.clone(),
+                                                index: portait_id.index,
+                                            },
+                                            texture: combat_asset.portrait_image.clone(),
+                                            ..default()
                                         },
-                                        texture: combat_asset.portrait_image.clone(),
-                                        ..default()
-                                    },
-                                    InZone::new(in_scene.zone_tag.as_str()),
-                                    OnCombatScreen,
-                                ));
+                                        InZone::new(in_scene.zone_tag.as_str()),
+                                        OnCombatScreen,
+                                    ))
+                                    .id();
+                                let mut combatant = Combatant::new(
+                                    combatant_id(entity),
+                                    Side::Director,
+                                    char.initiative,
+                                    char.vitality,
+                                    &in_scene.zone_tag,
+                                )
+                                .with_slug(&in_scene.entity_tag)
+                                .with_attacks(char.attacks.clone())
+                                .with_tags(char.tags.clone());
+                                if count > 1 {
+                                    combatant = combatant.with_squad(count as u8);
+                                    add_squad_badge(&mut commands, &asset_server, entity, count);
+                                }
+                                combatants.push(combatant);
                             }
                         }
                     }
                 }
             }
+            commands.insert_resource(Encounter(CombatState::new(combat_map, combatants)));
         }
     }
 
@@ -222,7 +536,7 @@ fn character_entity_and_portrait_for_tag(
 ) -> Option<(Entity, PortraitAtlasId)> {
     for (entity, name, _skills, portait_id) in characters.iter() {
         if &(name.slug) == tag {
-            return Some((entity.clone(), portait_id.clone()));
+            return Some((entity, portait_id.clone()));
         }
     }
     None
@@ -272,6 +586,33 @@ fn setup_combat_map(
     }
 }
 
+/// Show how many members of a squad are left on its token.
+fn add_squad_badge(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    token: Entity,
+    members: usize,
+) {
+    let badge = commands
+        .spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    format!("x{}", members),
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 24.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                transform: Transform::from_translation(Vec3::new(-30., -40., 1.)),
+                ..default()
+            },
+            SquadBadge,
+        ))
+        .id();
+    commands.entity(token).add_child(badge);
+}
+
 fn add_combat_token(
     commands: &mut Commands,
     combat_asset: &Res<CombatAsset>,
@@ -292,18 +633,37 @@ fn add_combat_token(
             texture: combat_asset.portrait_image.clone(),
             ..default()
         },
-        OnCombatScreen,
+        CombatToken,
     ));
 }
 
+/// Take the player characters off the map, keeping the characters themselves
+/// for the scenes that follow.
+fn clear_combat_tokens(mut commands: Commands, tokens: Query<Entity, With<CombatToken>>) {
+    for entity in tokens.iter() {
+        commands.entity(entity).despawn_descendants().remove::<(
+            SpriteSheetBundle,
+            CombatToken,
+            CurrentInitiative,
+            Initiative,
+            EquippedWeapon,
+            InZone,
+        )>();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn enable_buttons(
     mut commands: Commands,
+    encounter: Res<Encounter>,
     move_button: Query<Entity, With<MoveButton>>,
     extra_move_button: Query<Entity, With<ExtraMoveButton>>,
     attack_button: Query<Entity, With<AttackButton>>,
     switch_button: Query<Entity, With<SwitchButton>>,
     get_up_button: Query<Entity, With<GetUpButton>>,
+    reload_button: Query<Entity, With<ReloadButton>>,
     end_turn_button: Query<Entity, With<EndTurnButton>>,
+    check_offer: Res<CheckOffer>,
 ) {
     let Ok(move_button_entity) = move_button.get_single() else {
         return;
@@ -320,25 +680,50 @@ fn enable_buttons(
     let Ok(get_up_button_entity) = get_up_button.get_single() else {
         return;
     };
+    let Ok(reload_button_entity) = reload_button.get_single() else {
+        return;
+    };
     let Ok(end_turn_button_entity) = end_turn_button.get_single() else {
         return;
     };
-    commands.entity(move_button_entity).insert(ButtonEnabled);
-    commands
-        .entity(extra_move_button_entity)
-        .remove::<ButtonEnabled>();
-    commands
-        .entity(attack_button_entity)
-        .remove::<ButtonEnabled>();
-    commands
-        .entity(switch_button_entity)
-        .remove::<ButtonEnabled>();
-    commands
-        .entity(get_up_button_entity)
-        .remove::<ButtonEnabled>();
-    commands
-        .entity(end_turn_button_entity)
-        .insert(ButtonEnabled);
+    // Nothing else goes while a flip is being offered.
+    let players_turn = check_offer.0.is_none()
+        && encounter
+            .current()
+            .and_then(|actor| encounter.combatant(actor))
+            .map(|actor| actor.side == Side::Players)
+            .unwrap_or(false);
+    let turn = &encounter.this_turn;
+    let actor = encounter
+        .current()
+        .and_then(|actor| encounter.combatant(actor))
+        .filter(|_actor| players_turn);
+    let can_move = actor.is_some() && turn.can_move();
+    let can_extra_move = actor.is_some() && turn.can_extra_move();
+    let can_attack = actor.is_some_and(|actor| {
+        turn.can_attack(actor.attacks_per_turn())
+            && actor.is_loaded(actor.equipped)
+            && !encounter.targets(actor.id, actor.equipped).is_empty()
+    });
+    let can_reload =
+        actor.is_some_and(|actor| turn.can_reload() && actor.can_reload(actor.equipped));
+    let can_switch = actor.is_some_and(|actor| turn.can_switch() && actor.attacks.len() > 1);
+    let can_get_up = actor.is_some_and(|actor| actor.prone && turn.can_get_up());
+    set_enabled(&mut commands, move_button_entity, can_move);
+    set_enabled(&mut commands, extra_move_button_entity, can_extra_move);
+    set_enabled(&mut commands, attack_button_entity, can_attack);
+    set_enabled(&mut commands, switch_button_entity, can_switch);
+    set_enabled(&mut commands, get_up_button_entity, can_get_up);
+    set_enabled(&mut commands, reload_button_entity, can_reload);
+    set_enabled(&mut commands, end_turn_button_entity, players_turn);
+}
+
+fn set_enabled(commands: &mut Commands, button: Entity, enabled: bool) {
+    if enabled {
+        commands.entity(button).insert(ButtonEnabled);
+    } else {
+        commands.entity(button).remove::<ButtonEnabled>();
+    }
 }
 
 const DISABLED_BUTTON: Color = Color::rgb(0.05, 0.05, 0.05);
@@ -430,18 +815,21 @@ fn show_button_state(
 
 fn action_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands
-        .spawn(NodeBundle {
-            style: Style {
-                width: Val::Percent(100.0),
-                height: Val::Percent(100.0),
-                align_items: AlignItems::Center,
-                justify_content: JustifyContent::FlexEnd,
-                border: UiRect::all(Val::Px(2.0)),
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::FlexEnd,
+                    border: UiRect::all(Val::Px(2.0)),
+                    ..default()
+                },
+                border_color: BorderColor::from(Color::rgba(0.0, 0.0, 1.0, 0.5)),
                 ..default()
             },
-            border_color: BorderColor::from(Color::rgba(0.0, 0.0, 1.0, 0.5)),
-            ..default()
-        })
+            OnCombatScreen,
+        ))
         .with_children(|parent| {
             parent
                 .spawn(NodeBundle {
@@ -457,15 +845,65 @@ fn action_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
                     border_color: BorderColor::from(Color::rgba(0.5, 0.0, 0.0, 0.5)),
                     ..default()
                 })
+                .insert(ActionPanel)
                 .with_children(|parent| {
                     let font_handle = asset_server.load("fonts/FiraSans-Bold.ttf");
 
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "Round 1",
+                            TextStyle {
+                                font: font_handle.clone(),
+                                font_size: 30.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        ),
+                        RoundText,
+                    ));
+
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: font_handle.clone(),
+                                font_size: 16.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        ),
+                        ObjectiveText,
+                    ));
+
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: font_handle.clone(),
+                                font_size: 20.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        ),
+                        WeaponText,
+                    ));
+
                     add_button(parent, MoveButton, "Move", font_handle.clone());
                     add_button(parent, ExtraMoveButton, "Extra Move", font_handle.clone());
                     add_button(parent, AttackButton, "Attack", font_handle.clone());
                     add_button(parent, SwitchButton, "Switch Weapon", font_handle.clone());
+                    add_button(parent, ReloadButton, "Reload", font_handle.clone());
                     add_button(parent, GetUpButton, "Get Up", font_handle.clone());
                     add_button(parent, EndTurnButton, "End Turn", font_handle.clone());
+
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: font_handle.clone(),
+                                font_size: 16.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        ),
+                        CombatLogText,
+                    ));
                 });
         });
 }
@@ -506,6 +944,42 @@ fn add_button(
         });
 }
 
+fn add_menu_button(
+    parent: &mut ChildBuilder,
+    button_tag: impl Component,
+    button_text: &str,
+    font_handle: Handle<Font>,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(150.0),
+                    height: Val::Px(40.0),
+                    border: UiRect::all(Val::Px(3.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                border_color: BorderColor::from(Color::BLACK),
+                background_color: BackgroundColor::from(NORMAL_BUTTON),
+                ..default()
+            },
+            button_tag,
+            ButtonEnabled,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                button_text,
+                TextStyle {
+                    font: font_handle,
+                    font_size: 16.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            ));
+        });
+}
+
 fn add_zone(
     commands: &mut Commands,
     x_pos: f32,
@@ -591,14 +1065,118 @@ fn setup_zone_sprites(mut commands: Commands, zones: Query<(Entity, &ZoneArea, &
 }
 
 fn render_zones(
-    mut hoover_zones: Query<&mut Sprite, (With<ZoneArea>, With<HooverZone>)>,
-    mut other_zones: Query<&mut Sprite, (With<ZoneArea>, Without<HooverZone>)>,
+    mut zones: Query<
+        (
+            &mut Sprite,
+            Has<HooverZone>,
+            Has<AdjacentZone>,
+            Has<CharacterZone>,
+        ),
+        With<ZoneArea>,
+    >,
 ) {
-    for mut sprite in hoover_zones.iter_mut() {
-        sprite.color = Color::rgba(0.941, 0., 1., 0.5);
+    for (mut sprite, hoover, adjacent, character) in zones.iter_mut() {
+        sprite.color = match (hoover, adjacent, character) {
+            (true, true, _) => Color::rgba(0., 1., 0., 0.5),
+            (false, true, _) => Color::rgba(0., 1., 0., 0.2),
+            (_, false, true) => Color::rgba(1., 1., 0., 0.2),
+            (true, false, false) => Color::rgba(0.941, 0., 1., 0.5),
+            (false, false, false) => Color::rgba(0., 0., 1., 0.),
+        };
     }
-    for mut sprite in other_zones.iter_mut() {
-        sprite.color = Color::rgba(0., 0., 1., 0.);
+}
+
+fn show_move_zones(
+    mut commands: Commands,
+    encounter: Res<Encounter>,
+    move_button: Query<
+        (),
+        (
+            Or<(With<MoveButton>, With<ExtraMoveButton>)>,
+            Added<ButtonPressed>,
+        ),
+    >,
+    zones: Query<(Entity, &ZoneName)>,
+) {
+    if move_button.is_empty() {
+        return;
+    }
+    let Some(actor) = encounter.current().and_then(|id| encounter.combatant(id)) else {
+        return;
+    };
+    let adjacent = encounter.adjacent_zones(&actor.zone);
+    for (entity, name) in zones.iter() {
+        if name.tag == actor.zone {
+            commands.entity(entity).insert(CharacterZone);
+        } else if adjacent.iter().any(|zone| zone.tag == name.tag) {
+            commands.entity(entity).insert(AdjacentZone);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn choose_move_zone(
+    mut commands: Commands,
+    mut encounter: ResMut<Encounter>,
+    mut dice: ResMut<SeededDice>,
+    mut check_offer: ResMut<CheckOffer>,
+    mut rules_events: EventWriter<RulesEvent>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    move_button: Query<
+        Entity,
+        (
+            Or<(With<MoveButton>, With<ExtraMoveButton>)>,
+            With<ButtonPressed>,
+        ),
+    >,
+    zones: Query<&ZoneName, (With<AdjacentZone>, With<HooverZone>)>,
+    ui_buttons: Query<&Interaction, With<Button>>,
+    target_menu: Query<Entity, With<TargetMenu>>,
+) {
+    if move_button.is_empty() || !mouse_buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    if ui_buttons
+        .iter()
+        .any(|interaction| *interaction != Interaction::None)
+    {
+        return;
+    }
+    let Ok(zone) = zones.get_single() else {
+        return;
+    };
+    let action = Action::Move {
+        to: zone.tag.clone(),
+    };
+    apply_action(
+        &mut encounter,
+        &action,
+        &mut dice,
+        &mut check_offer,
+        &mut rules_events,
+    );
+    close_menu(&mut commands, &target_menu, &move_button);
+}
+
+fn clear_move_zones(
+    mut commands: Commands,
+    move_button: Query<
+        (),
+        (
+            Or<(With<MoveButton>, With<ExtraMoveButton>)>,
+            With<ButtonPressed>,
+        ),
+    >,
+    zones: Query<Entity, Or<(With<AdjacentZone>, With<CharacterZone>)>>,
+) {
+    if !move_button.is_empty() {
+        return;
+    }
+    for entity in zones.iter() {
+        commands
+            .entity(entity)
+            .remove::<AdjacentZone>()
+            .remove::<CharacterZone>();
     }
 }
 
@@ -619,18 +1197,887 @@ fn resolve_zones(
     }
 }
 
-fn set_starting_initiative(mut commands: Commands, characters: Query<(Entity, &Initiative)>) {
-    let mut start_player: Option<Entity> = None;
-    let mut highest_initiative = 0;
-    for (entity, initiative) in characters.iter() {
-        debug!("{:?}::{:?} > {:?}", entity, initiative, highest_initiative);
-        if initiative.value > highest_initiative {
-            start_player = Some(entity);
-            highest_initiative = initiative.value;
+fn set_starting_initiative(
+    mut encounter: ResMut<Encounter>,
+    mut dice: ResMut<SeededDice>,
+    mut check_offer: ResMut<CheckOffer>,
+    mut rules_events: EventWriter<RulesEvent>,
+) {
+    check_offer.0 = None;
+    resolve_action(
+        &mut encounter,
+        None,
+        &mut dice,
+        &mut check_offer,
+        &mut rules_events,
+    );
+    info!("turn order: {:?}", encounter.turn_order);
+}
+
+/// Begin the combat, or resolve an action taken in it.
+fn resolve(
+    encounter: &Encounter,
+    action: Option<&Action>,
+    dice: &mut SeededDice,
+    flipper: &mut PromptedSpend,
+) -> Result<(CombatState, Vec<CombatEvent>), RulesError> {
+    match action {
+        Some(action) => encounter.apply_with_flips(action, dice, flipper),
+        None => encounter.begin_with_flips(dice, flipper),
+    }
+}
+
+fn apply_action(
+    encounter: &mut Encounter,
+    action: &Action,
+    dice: &mut SeededDice,
+    check_offer: &mut CheckOffer,
+    rules_events: &mut EventWriter<RulesEvent>,
+) -> bool {
+    resolve_action(encounter, Some(action), dice, check_offer, rules_events)
+}
+
+/// Begin the combat or apply an action to it, or hold it back on a check
+/// the player may spend on. Gives whether the rules allowed it.
+fn resolve_action(
+    encounter: &mut Encounter,
+    action: Option<&Action>,
+    dice: &mut SeededDice,
+    check_offer: &mut CheckOffer,
+    rules_events: &mut EventWriter<RulesEvent>,
+) -> bool {
+    let before = dice.clone();
+    let mut flipper = PromptedSpend::default();
+    match resolve(encounter, action, dice, &mut flipper) {
+        Ok((state, events)) => {
+            // Hold the action back until the player has answered the prompt.
+            if let Some((roller, check, choices)) = flipper.offered {
+                if !choices.is_empty() {
+                    check_offer.0 = Some(PendingCheck {
+                        action: action.cloned(),
+                        dice: before,
+                        roller,
+                        check,
+                        choices,
+                    });
+                    return true;
+                }
+            }
+            encounter.0 = state;
+            rules_events.send_batch(events.into_iter().map(RulesEvent));
+            true
+        }
+        Err(error) => {
+            match action {
+                Some(action) => warn!("{:?} not allowed: {}", action, error),
+                None => warn!("combat could not begin: {}", error),
+            }
+            false
         }
     }
-    if let Some(entity) = start_player {
-        commands.entity(entity).insert(CurrentInitiative);
+}
+
+/// Resolve the held back action again with the same dice, spending what the
+/// player chose on the offered check or keeping it.
+fn answer_check_offer(
+    mut encounter: ResMut<Encounter>,
+    mut dice: ResMut<SeededDice>,
+    mut check_offer: ResMut<CheckOffer>,
+    mut rules_events: EventWriter<RulesEvent>,
+    spend_buttons: Query<(&Interaction, &SpendButton), Changed<Interaction>>,
+    keep_buttons: Query<&Interaction, (With<KeepButton>, Changed<Interaction>)>,
+) {
+    let pressed = |interaction: &Interaction| *interaction == Interaction::Pressed;
+    let answer = if let Some((_interaction, spend)) = spend_buttons
+        .iter()
+        .find(|(interaction, _spend)| pressed(interaction))
+    {
+        Some(spend.0)
+    } else if keep_buttons.iter().any(pressed) {
+        None
+    } else {
+        return;
+    };
+    let Some(pending) = check_offer.0.take() else {
+        return;
+    };
+    let mut replay_dice = pending.dice;
+    let mut flipper = PromptedSpend {
+        answer,
+        ..default()
+    };
+    match resolve(
+        &encounter,
+        pending.action.as_ref(),
+        &mut replay_dice,
+        &mut flipper,
+    ) {
+        Ok((state, events)) => {
+            encounter.0 = state;
+            *dice = replay_dice;
+            rules_events.send_batch(events.into_iter().map(RulesEvent));
+        }
+        Err(error) => warn!("{:?} not allowed: {}", pending.action, error),
+    }
+}
+
+fn show_check_offer(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    encounter: Res<Encounter>,
+    check_offer: Res<CheckOffer>,
+    prompts: Query<Entity, With<CheckPrompt>>,
+    names: Query<(Option<&CharacterName>, Option<&NoName>)>,
+) {
+    if !check_offer.is_changed() {
+        return;
+    }
+    for entity in prompts.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some(pending) = &check_offer.0 else {
+        return;
+    };
+    let question = format!(
+        "{} rolled {} against {}.\n({} flips and {} story points left)",
+        combatant_name(&names, pending.roller),
+        pending.check.roll.value(),
+        pending.check.target,
+        encounter.flips_left(pending.roller),
+        encounter
+            .combatant(pending.roller)
+            .map(|combatant| combatant.story_points)
+            .unwrap_or_default(),
+    );
+    let flipped = pending.check.flipped().roll.value();
+    let font_handle = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    position_type: PositionType::Absolute,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            CheckPrompt,
+            OnCombatScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        row_gap: Val::Px(20.0),
+                        padding: UiRect::all(Val::Px(40.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        question,
+                        TextStyle {
+                            font: font_handle.clone(),
+                            font_size: 20.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                    ));
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                column_gap: Val::Px(20.0),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for choice in pending.choices.iter() {
+                                let label = match choice {
+                                    CheckChoice::Flip => format!("Flip to {}", flipped),
+                                    CheckChoice::Story(StorySpend::Flip) => {
+                                        format!("Story point: flip to {}", flipped)
+                                    }
+                                    CheckChoice::Story(StorySpend::Reroll) => {
+                                        "Story point: reroll".to_string()
+                                    }
+                                    CheckChoice::Story(StorySpend::SignatureGear) => format!(
+                                        "Story point: signature gear +{}",
+                                        SIGNATURE_GEAR_BONUS
+                                    ),
+                                };
+                                add_menu_button(
+                                    parent,
+                                    SpendButton(*choice),
+                                    &label,
+                                    font_handle.clone(),
+                                );
+                            }
+                            add_menu_button(parent, KeepButton, "Keep", font_handle.clone());
+                        });
+                });
+        });
+}
+
+fn get_up(
+    mut commands: Commands,
+    mut encounter: ResMut<Encounter>,
+    mut dice: ResMut<SeededDice>,
+    mut check_offer: ResMut<CheckOffer>,
+    mut rules_events: EventWriter<RulesEvent>,
+    get_up_button: Query<Entity, (With<GetUpButton>, With<ButtonPressed>)>,
+) {
+    let Ok(button_entity) = get_up_button.get_single() else {
+        return;
+    };
+    commands.entity(button_entity).remove::<ButtonPressed>();
+    apply_action(
+        &mut encounter,
+        &Action::GetUp,
+        &mut dice,
+        &mut check_offer,
+        &mut rules_events,
+    );
+}
+
+fn switch_weapon(
+    mut commands: Commands,
+    mut encounter: ResMut<Encounter>,
+    mut dice: ResMut<SeededDice>,
+    mut check_offer: ResMut<CheckOffer>,
+    mut rules_events: EventWriter<RulesEvent>,
+    switch_button: Query<Entity, (With<SwitchButton>, With<ButtonPressed>)>,
+) {
+    let Ok(button_entity) = switch_button.get_single() else {
+        return;
+    };
+    commands.entity(button_entity).remove::<ButtonPressed>();
+    apply_action(
+        &mut encounter,
+        &Action::SwitchWeapon,
+        &mut dice,
+        &mut check_offer,
+        &mut rules_events,
+    );
+}
+
+fn reload(
+    mut commands: Commands,
+    mut encounter: ResMut<Encounter>,
+    mut dice: ResMut<SeededDice>,
+    mut check_offer: ResMut<CheckOffer>,
+    mut rules_events: EventWriter<RulesEvent>,
+    reload_button: Query<Entity, (With<ReloadButton>, With<ButtonPressed>)>,
+) {
+    let Ok(button_entity) = reload_button.get_single() else {
+        return;
+    };
+    commands.entity(button_entity).remove::<ButtonPressed>();
+    let Some(actor) = encounter.current().and_then(|id| encounter.combatant(id)) else {
+        return;
+    };
+    let action = Action::Reload {
+        attack: actor.equipped,
+    };
+    apply_action(
+        &mut encounter,
+        &action,
+        &mut dice,
+        &mut check_offer,
+        &mut rules_events,
+    );
+}
+
+fn end_turn(
+    mut commands: Commands,
+    mut encounter: ResMut<Encounter>,
+    mut dice: ResMut<SeededDice>,
+    mut check_offer: ResMut<CheckOffer>,
+    mut rules_events: EventWriter<RulesEvent>,
+    end_turn_button: Query<Entity, (With<EndTurnButton>, With<ButtonPressed>)>,
+) {
+    let Ok(button_entity) = end_turn_button.get_single() else {
+        return;
+    };
+    commands.entity(button_entity).remove::<ButtonPressed>();
+    apply_action(
+        &mut encounter,
+        &Action::EndTurn,
+        &mut dice,
+        &mut check_offer,
+        &mut rules_events,
+    );
+}
+
+fn director_turn(
+    time: Res<Time>,
+    mut director: ResMut<DirectorTurn>,
+    mut encounter: ResMut<Encounter>,
+    mut dice: ResMut<SeededDice>,
+    mut check_offer: ResMut<CheckOffer>,
+    mut rules_events: EventWriter<RulesEvent>,
+) {
+    if check_offer.0.is_some() {
+        return;
+    }
+    let Some(actor) = encounter
+        .current()
+        .and_then(|actor| encounter.combatant(actor))
+        .filter(|actor| actor.side == Side::Director)
+    else {
+        return;
+    };
+    if !director.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let action = behaviour_for(&actor.tags).next_action(&encounter, actor.id, &encounter.this_turn);
+    debug!("director {:?}: {:?}", actor.id, action);
+    let allowed = apply_action(
+        &mut encounter,
+        &action,
+        &mut dice,
+        &mut check_offer,
+        &mut rules_events,
+    );
+    // Pass rather than try the same thing again on the next tick.
+    if !allowed && action != Action::EndTurn {
+        apply_action(
+            &mut encounter,
+            &Action::EndTurn,
+            &mut dice,
+            &mut check_offer,
+            &mut rules_events,
+        );
+    }
+}
+
+fn show_move_cancel(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    move_button: Query<
+        (),
+        (
+            Or<(With<MoveButton>, With<ExtraMoveButton>)>,
+            Added<ButtonPressed>,
+        ),
+    >,
+    panel: Query<Entity, With<ActionPanel>>,
+) {
+    if move_button.is_empty() {
+        return;
+    }
+    let Ok(panel_entity) = panel.get_single() else {
+        return;
+    };
+    let font_handle = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands.entity(panel_entity).with_children(|parent| {
+        parent
+            .spawn((NodeBundle::default(), TargetMenu))
+            .with_children(|parent| {
+                add_menu_button(parent, CancelButton, "Cancel", font_handle);
+            });
+    });
+}
+
+fn show_attack_targets(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    encounter: Res<Encounter>,
+    attack_button: Query<(), (With<AttackButton>, Added<ButtonPressed>)>,
+    panel: Query<Entity, With<ActionPanel>>,
+    names: Query<(Option<&CharacterName>, Option<&NoName>)>,
+) {
+    if attack_button.is_empty() {
+        return;
+    }
+    let Ok(panel_entity) = panel.get_single() else {
+        return;
+    };
+    let Some(attacker) = encounter.current().and_then(|id| encounter.combatant(id)) else {
+        return;
+    };
+    let font_handle = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands.entity(panel_entity).with_children(|parent| {
+        parent
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        ..default()
+                    },
+                    ..default()
+                },
+                TargetMenu,
+            ))
+            .with_children(|parent| {
+                for target in encounter.targets(attacker.id, attacker.equipped) {
+                    add_menu_button(
+                        parent,
+                        TargetButton(target),
+                        &combatant_name(&names, target),
+                        font_handle.clone(),
+                    );
+                }
+                add_menu_button(parent, CancelButton, "Cancel", font_handle.clone());
+            });
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn choose_attack_target(
+    mut commands: Commands,
+    mut encounter: ResMut<Encounter>,
+    mut dice: ResMut<SeededDice>,
+    mut check_offer: ResMut<CheckOffer>,
+    mut rules_events: EventWriter<RulesEvent>,
+    target_buttons: Query<(&Interaction, &TargetButton), Changed<Interaction>>,
+    attack_button: Query<Entity, (With<AttackButton>, With<ButtonPressed>)>,
+    target_menu: Query<Entity, With<TargetMenu>>,
+) {
+    for (interaction, target) in target_buttons.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Some(attacker) = encounter.current().and_then(|id| encounter.combatant(id)) else {
+            continue;
+        };
+        let action = Action::Attack {
+            target: target.0,
+            attack: attacker.equipped,
+        };
+        apply_action(
+            &mut encounter,
+            &action,
+            &mut dice,
+            &mut check_offer,
+            &mut rules_events,
+        );
+        close_menu(&mut commands, &target_menu, &attack_button);
+    }
+}
+
+fn cancel_action(
+    mut commands: Commands,
+    cancel_buttons: Query<&Interaction, (With<CancelButton>, Changed<Interaction>)>,
+    pressed_buttons: Query<Entity, With<ButtonPressed>>,
+    target_menu: Query<Entity, With<TargetMenu>>,
+) {
+    if cancel_buttons
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        close_menu(&mut commands, &target_menu, &pressed_buttons);
+    }
+}
+
+fn close_menu<F: bevy::ecs::query::QueryFilter>(
+    commands: &mut Commands,
+    target_menu: &Query<Entity, With<TargetMenu>>,
+    pressed_buttons: &Query<Entity, F>,
+) {
+    for entity in target_menu.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for entity in pressed_buttons.iter() {
+        commands.entity(entity).remove::<ButtonPressed>();
+    }
+}
+
+fn combatant_name(
+    names: &Query<(Option<&CharacterName>, Option<&NoName>)>,
+    id: CombatantId,
+) -> String {
+    match names.get(combatant_entity(id)) {
+        Ok((Some(name), _)) => name.alias.clone(),
+        Ok((None, Some(name))) => name.alias.clone(),
+        _ => format!("{:?}", id),
+    }
+}
+
+fn log_rules_events(
+    mut combat_log: ResMut<CombatLog>,
+    mut rules_events: EventReader<RulesEvent>,
+    names: Query<(Option<&CharacterName>, Option<&NoName>)>,
+) {
+    for event in rules_events.read() {
+        let line = match &**event {
+            CombatEvent::RoundStarted { round } => format!("Round {}", round),
+            CombatEvent::TurnStarted { combatant } => {
+                format!("{} acts", combatant_name(&names, *combatant))
+            }
+            CombatEvent::Moved { combatant, to, .. } => {
+                format!("{} moves to {}", combatant_name(&names, *combatant), to)
+            }
+            CombatEvent::MoveChecked {
+                combatant,
+                to,
+                skill,
+                check,
+            } => format!(
+                "{} tries {:?} to reach {}: {} vs {} {:?}",
+                combatant_name(&names, *combatant),
+                skill,
+                to,
+                check.roll.value(),
+                check.target,
+                check.outcome,
+            ),
+            CombatEvent::Attacked {
+                attacker,
+                target,
+                attack,
+                check,
+            } => format!(
+                "{} attacks {} with {}: {} vs {} {:?}",
+                combatant_name(&names, *attacker),
+                combatant_name(&names, *target),
+                attack,
+                check.roll.value(),
+                check.target,
+                check.outcome,
+            ),
+            CombatEvent::Damaged {
+                combatant,
+                damage,
+                vitality,
+            } => format!(
+                "{} takes {} damage, {} vitality left",
+                combatant_name(&names, *combatant),
+                damage,
+                vitality
+            ),
+            CombatEvent::Incapacitated { combatant } => {
+                format!("{} is down", combatant_name(&names, *combatant))
+            }
+            CombatEvent::ComplicationTriggered {
+                combatant,
+                complication,
+            } => format!(
+                "{}'s {:?} gets in the way, +{} story point",
+                combatant_name(&names, *combatant),
+                complication,
+                COMPLICATION_AWARD
+            ),
+            CombatEvent::SurpriseChecked { combatant, check } => format!(
+                "{} tries Alertness to react: {} vs {} {:?}",
+                combatant_name(&names, *combatant),
+                check.roll.value(),
+                check.target,
+                check.outcome,
+            ),
+            CombatEvent::SquadMemberDown { combatant, left } => format!(
+                "{} loses a member, {} left",
+                combatant_name(&names, *combatant),
+                left
+            ),
+            CombatEvent::FellProne { combatant } => {
+                format!("{} falls prone", combatant_name(&names, *combatant))
+            }
+            CombatEvent::GotUp { combatant } => {
+                format!("{} gets up", combatant_name(&names, *combatant))
+            }
+            CombatEvent::WeaponSwitched {
+                combatant, weapon, ..
+            } => format!(
+                "{} switches to {}",
+                combatant_name(&names, *combatant),
+                weapon
+            ),
+            CombatEvent::Reloaded { combatant, .. } => {
+                format!("{} reloads", combatant_name(&names, *combatant))
+            }
+            CombatEvent::TurnSkipped { combatant } => {
+                format!("{} loses the turn", combatant_name(&names, *combatant))
+            }
+            CombatEvent::StatusApplied {
+                combatant,
+                status,
+                rounds,
+            } => format!(
+                "{} is {:?} for {} rounds",
+                combatant_name(&names, *combatant),
+                status,
+                rounds
+            ),
+            CombatEvent::StatusChecked {
+                combatant,
+                status,
+                skill,
+                check,
+            } => format!(
+                "{} tries {:?} against {:?}: {} vs {} {:?}",
+                combatant_name(&names, *combatant),
+                skill,
+                status,
+                check.roll.value(),
+                check.target,
+                check.outcome,
+            ),
+            CombatEvent::StatusExpired { combatant, status } => format!(
+                "{} is no longer {:?}",
+                combatant_name(&names, *combatant),
+                status
+            ),
+            CombatEvent::Flipped {
+                combatant,
+                from,
+                to,
+                flips_left,
+            } => format!(
+                "{} flips {} to {}, {} flips left",
+                combatant_name(&names, *combatant),
+                from.value(),
+                to.value(),
+                flips_left
+            ),
+            CombatEvent::StoryPointSpent {
+                combatant,
+                spend,
+                from,
+                to,
+                story_points_left,
+            } => format!(
+                "{} spends a story point on {:?}, {} to {}, {} left",
+                combatant_name(&names, *combatant),
+                spend,
+                from.roll.value(),
+                to.roll.value(),
+                story_points_left
+            ),
+            CombatEvent::TurnEnded { .. } | CombatEvent::AmmoSpent { .. } => continue,
+        };
+        combat_log.push(line);
+    }
+}
+
+fn show_combat_log(combat_log: Res<CombatLog>, mut query: Query<&mut Text, With<CombatLogText>>) {
+    if !combat_log.is_changed() {
+        return;
+    }
+    let lines: Vec<&str> = combat_log.0.iter().map(String::as_str).collect();
+    for mut text in query.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn apply_rules_events(
+    mut commands: Commands,
+    mut rules_events: EventReader<RulesEvent>,
+    current: Query<Entity, With<CurrentInitiative>>,
+    mut located_objects: Query<&mut InZone>,
+    mut vitalities: Query<&mut Vitality>,
+    mut tokens: Query<(&mut Sprite, &mut Transform), With<InZone>>,
+    mut weapons: Query<&mut EquippedWeapon>,
+    status_icons: Query<(Entity, &Parent, &StatusIcon)>,
+    mut squad_badges: Query<(&Parent, &mut Text), With<SquadBadge>>,
+    zones: Query<(Entity, &ZoneName)>,
+    asset_server: Res<AssetServer>,
+) {
+    for event in rules_events.read() {
+        debug!("rules: {:?}", **event);
+        match &**event {
+            CombatEvent::TurnStarted { combatant } => {
+                for entity in current.iter() {
+                    commands.entity(entity).remove::<CurrentInitiative>();
+                }
+                commands
+                    .entity(combatant_entity(*combatant))
+                    .insert(CurrentInitiative);
+            }
+            CombatEvent::Moved { combatant, to, .. } => {
+                if let Ok(mut in_zone) = located_objects.get_mut(combatant_entity(*combatant)) {
+                    in_zone.name = to.clone();
+                    in_zone.area = zones
+                        .iter()
+                        .find(|(_entity, name)| &name.tag == to)
+                        .map(|(entity, _name)| entity);
+                }
+            }
+            CombatEvent::Damaged {
+                combatant,
+                vitality,
+                ..
+            } => {
+                if let Ok(mut character_vitality) = vitalities.get_mut(combatant_entity(*combatant))
+                {
+                    character_vitality.value = *vitality;
+                }
+            }
+            CombatEvent::Incapacitated { combatant } => {
+                if let Ok((mut sprite, _transform)) = tokens.get_mut(combatant_entity(*combatant)) {
+                    sprite.color = Color::DARK_GRAY;
+                }
+            }
+            CombatEvent::FellProne { combatant } => {
+                if let Ok((_sprite, mut transform)) = tokens.get_mut(combatant_entity(*combatant)) {
+                    transform.rotation = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+                }
+            }
+            CombatEvent::WeaponSwitched {
+                combatant,
+                equipped,
+                weapon,
+            } => {
+                if let Ok(mut equipped_weapon) = weapons.get_mut(combatant_entity(*combatant)) {
+                    equipped_weapon.index = *equipped;
+                    equipped_weapon.name = weapon.clone();
+                }
+            }
+            CombatEvent::GotUp { combatant } => {
+                if let Ok((_sprite, mut transform)) = tokens.get_mut(combatant_entity(*combatant)) {
+                    transform.rotation = Quat::IDENTITY;
+                }
+            }
+            CombatEvent::StatusApplied {
+                combatant, status, ..
+            } => {
+                let entity = combatant_entity(*combatant);
+                let shown = status_icons
+                    .iter()
+                    .any(|(_icon, parent, icon)| parent.get() == entity && icon.0 == *status);
+                if !shown {
+                    add_status_icon(&mut commands, &asset_server, entity, *status);
+                }
+            }
+            CombatEvent::SquadMemberDown { combatant, left } => {
+                for (parent, mut text) in squad_badges.iter_mut() {
+                    if parent.get() == combatant_entity(*combatant) {
+                        text.sections[0].value = format!("x{}", left);
+                    }
+                }
+            }
+            CombatEvent::StatusExpired { combatant, status } => {
+                let entity = combatant_entity(*combatant);
+                for (icon_entity, parent, icon) in status_icons.iter() {
+                    if parent.get() == entity && icon.0 == *status {
+                        commands.entity(icon_entity).despawn_recursive();
+                    }
+                }
+            }
+            CombatEvent::RoundStarted { .. }
+            | CombatEvent::TurnEnded { .. }
+            | CombatEvent::TurnSkipped { .. }
+            | CombatEvent::SurpriseChecked { .. }
+            | CombatEvent::ComplicationTriggered { .. }
+            | CombatEvent::Flipped { .. }
+            | CombatEvent::StoryPointSpent { .. }
+            | CombatEvent::StatusChecked { .. }
+            | CombatEvent::MoveChecked { .. }
+            | CombatEvent::Attacked { .. }
+            | CombatEvent::AmmoSpent { .. }
+            | CombatEvent::Reloaded { .. } => {}
+        }
+    }
+}
+
+/// The letter and colour standing for a status effect over a token.
+fn status_icon(status: StatusKind) -> (&'static str, Color) {
+    match status {
+        StatusKind::Paralysed => ("P", Color::rgb(0.3, 0.6, 1.0)),
+    }
+}
+
+fn add_status_icon(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    token: Entity,
+    status: StatusKind,
+) {
+    let (letter, color) = status_icon(status);
+    let icon = commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(Vec2::new(24., 24.)),
+                    ..default()
+                },
+                transform: Transform::from_translation(Vec3::new(30., 40., 1.)),
+                ..default()
+            },
+            StatusIcon(status),
+        ))
+        .with_children(|parent| {
+            parent.spawn(Text2dBundle {
+                text: Text::from_section(
+                    letter,
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 20.0,
+                        color: Color::BLACK,
+                    },
+                ),
+                transform: Transform::from_translation(Vec3::new(0., 0., 1.)),
+                ..default()
+            });
+        })
+        .id();
+    commands.entity(token).add_child(icon);
+}
+
+/// Keep the story points of the player characters up to date with the ones
+/// they earn and spend in combat.
+fn sync_story_points(
+    encounter: Res<Encounter>,
+    mut rules_events: EventReader<RulesEvent>,
+    mut story_points: ResMut<StoryPoints>,
+) {
+    for event in rules_events.read() {
+        let combatant = match &**event {
+            CombatEvent::ComplicationTriggered { combatant, .. }
+            | CombatEvent::StoryPointSpent { combatant, .. } => combatant,
+            _ => continue,
+        };
+        if let Some(pc) = encounter
+            .combatant(*combatant)
+            .filter(|combatant| combatant.side == Side::Players)
+        {
+            story_points.set(&pc.slug, pc.story_points);
+        }
+    }
+}
+
+fn show_round(encounter: Res<Encounter>, mut query: Query<&mut Text, With<RoundText>>) {
+    if !encounter.is_changed() {
+        return;
+    }
+    for mut text in query.iter_mut() {
+        text.sections[0].value = if encounter.is_surprise_round() {
+            format!("Round {} (surprise)", encounter.round())
+        } else {
+            format!("Round {}", encounter.round())
+        };
+    }
+}
+
+fn show_weapon(
+    encounter: Res<Encounter>,
+    weapons: Query<&EquippedWeapon, With<CurrentInitiative>>,
+    mut query: Query<&mut Text, With<WeaponText>>,
+) {
+    let weapon = match weapons.get_single() {
+        Ok(weapon) => {
+            let ammo = encounter
+                .current()
+                .and_then(|id| encounter.combatant(id))
+                .and_then(|actor| Some((actor.ammo(weapon.index)?, actor.weapon()?.capacity()?)))
+                .map(|(ammo, shots)| format!(" ({}/{})", ammo, shots))
+                .unwrap_or_default();
+            format!("Weapon: {}{}", weapon.name, ammo)
+        }
+        Err(_) => String::new(),
+    };
+    for mut text in query.iter_mut() {
+        if text.sections[0].value != weapon {
+            text.sections[0].value = weapon.clone();
+        }
     }
 }
 
@@ -711,3 +2158,148 @@ impl InZone {
         }
     }
 }
+
+fn show_objectives(
+    encounter: Res<Encounter>,
+    zones: Query<&ZoneName>,
+    mut query: Query<&mut Text, With<ObjectiveText>>,
+) {
+    if !encounter.is_changed() {
+        return;
+    }
+    let zone_name = |tag: &str| {
+        zones
+            .iter()
+            .find(|zone| zone.tag == tag)
+            .map(|zone| zone.name.clone())
+            .unwrap_or(tag.to_string())
+    };
+    let lines: Vec<String> = encounter
+        .objectives
+        .iter()
+        .map(|objective| {
+            let (done, needed) = encounter.objective_progress(objective);
+            let goal = match objective {
+                Objective::Reach { zone, .. } => format!("Reach {}", zone_name(zone)),
+                Objective::Survive { rounds } => format!("Survive {} rounds", rounds),
+                Objective::Defeat { tag } => format!("Defeat {}", tag),
+                Objective::Protect { tag } => format!("Protect {}", tag),
+                Objective::Escape { zone } => format!("Escape through {}", zone_name(zone)),
+            };
+            let status = match encounter.objective_status(objective) {
+                ObjectiveStatus::Pending => format!("{}/{}", done, needed),
+                ObjectiveStatus::Met => "done".to_string(),
+                ObjectiveStatus::Failed => "failed".to_string(),
+            };
+            format!("{}: {}", goal, status)
+        })
+        .collect();
+    for mut text in query.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+fn check_combat_end(
+    encounter: Res<Encounter>,
+    mut last_combat: ResMut<LastCombat>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    if !encounter.is_changed() {
+        return;
+    }
+    if let Some(result) = encounter.result() {
+        info!("combat ended: {:?}", result);
+        last_combat.0 = Some(result);
+        game_state.set(GameState::CombatEnded);
+    }
+}
+
+fn show_combat_result(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    last_combat: Res<LastCombat>,
+    names: Query<(Option<&CharacterName>, Option<&NoName>)>,
+) {
+    let Some(result) = &last_combat.0 else {
+        return;
+    };
+    let title = match result.outcome {
+        Outcome::Victory => "Victory!",
+        Outcome::Defeat => "Defeat",
+        Outcome::Fled => "Got away",
+    };
+    let mut summary = format!("The fight lasted {} rounds.", result.rounds);
+    if !result.down.is_empty() {
+        let down: Vec<String> = result
+            .down
+            .iter()
+            .map(|id| combatant_name(&names, *id))
+            .collect();
+        summary.push_str(&format!("\nDown: {}", down.join(", ")));
+    }
+    let font_handle = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    position_type: PositionType::Absolute,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            OnCombatScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        row_gap: Val::Px(20.0),
+                        padding: UiRect::all(Val::Px(40.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        title,
+                        TextStyle {
+                            font: font_handle.clone(),
+                            font_size: 60.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                    ));
+                    parent.spawn(TextBundle::from_section(
+                        summary,
+                        TextStyle {
+                            font: font_handle.clone(),
+                            font_size: 20.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                    ));
+                    add_menu_button(parent, ContinueButton, "Continue", font_handle.clone());
+                });
+        });
+}
+
+fn leave_combat(
+    last_combat: Res<LastCombat>,
+    current_scene: Res<CurrentScene>,
+    mut next_scene: ResMut<NextScene>,
+    mut game_state: ResMut<NextState<GameState>>,
+    continue_buttons: Query<&Interaction, (With<ContinueButton>, Changed<Interaction>)>,
+) {
+    if continue_buttons
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        let outcome = last_combat.0.as_ref().map(|result| result.outcome);
+        current_scene.finish(outcome, &mut next_scene, &mut game_state);
+    }
+}
@@ -8,8 +8,14 @@ use bevy::{
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-#[derive(Debug, Deserialize, Serialize)]
+use crate::rules::checks::{CheckSpec, CheckSpecError};
+use crate::rules::Side;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ZoneMove {
+    /// A skill check needed to make the move, e.g. `"Agility"` or
+    /// `"Security:-20"`. Failing it leaves the character where they are,
+    /// knocked prone.
     pub check: Option<String>,
     pub tag: String,
 }
@@ -36,11 +42,104 @@ pub struct StartPosition {
     pub zone_tag: String,
 }
 
+/// What winning, or losing, means on a map.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub enum Objective {
+    /// Get `count` player characters into the zone, or everyone still
+    /// standing when no count is given.
+    Reach {
+        zone: String,
+        #[serde(default)]
+        count: Option<usize>,
+    },
+    /// Hold out until the given number of rounds is over.
+    Survive { rounds: u32 },
+    /// Take down every character with the tag.
+    Defeat { tag: String },
+    /// Lose as soon as a character with the tag goes down.
+    Protect { tag: String },
+    /// Get the player characters away through the zone, the fight ending as
+    /// fled rather than won once every one of them still standing is in it.
+    /// Director characters never leave this way.
+    Escape { zone: String },
+}
+
 #[derive(Asset, TypePath, Debug, Deserialize, Serialize)]
 pub struct CombatMap {
     pub bitmap: String,
     pub zones: Vec<MapZone>,
     pub start_positions: Vec<StartPosition>,
+    #[serde(default)]
+    pub objectives: Vec<Objective>,
+    /// Group mooks of the same kind starting in the same zone into squads.
+    #[serde(default)]
+    pub squads: bool,
+    /// The side caught by surprise when the fight breaks out, if any.
+    #[serde(default)]
+    pub surprised: Option<Side>,
+}
+
+impl CombatMap {
+    /// The start positions, with those that `in_squad` picks out merged per
+    /// character and zone when the map has squads, each with how many stand
+    /// there.
+    pub fn start_groups(
+        &self,
+        in_squad: impl Fn(&StartPosition) -> bool,
+    ) -> Vec<(&StartPosition, usize)> {
+        let mut groups: Vec<(&StartPosition, usize)> = Vec::new();
+        for start in self.start_positions.iter() {
+            let squad = self.squads && in_squad(start);
+            match groups.iter_mut().find(|(grouped, _count)| {
+                squad
+                    && grouped.entity_tag == start.entity_tag
+                    && grouped.zone_tag == start.zone_tag
+            }) {
+                Some((_grouped, count)) => *count += 1,
+                None => groups.push((start, 1)),
+            }
+        }
+        groups
+    }
+
+    /// Check that every move check names a skill and every objective a zone,
+    /// so map mistakes show up when the map is loaded rather than in the
+    /// middle of combat.
+    pub fn validate(&self) -> Result<(), CombatMapAssetLoaderError> {
+        for zone in self.zones.iter() {
+            for zone_move in zone.adjacent.iter() {
+                if let Some(check) = &zone_move.check {
+                    check.parse::<CheckSpec>().map_err(|error| {
+                        CombatMapAssetLoaderError::InvalidCheck {
+                            zone: zone.tag.clone(),
+                            to: zone_move.tag.clone(),
+                            error,
+                        }
+                    })?;
+                }
+            }
+        }
+        for objective in self.objectives.iter() {
+            match objective {
+                Objective::Reach { zone, .. } | Objective::Escape { zone } => {
+                    if !self.zones.iter().any(|map_zone| &map_zone.tag == zone) {
+                        return Err(CombatMapAssetLoaderError::UnknownZone(zone.clone()));
+                    }
+                }
+                Objective::Defeat { tag } | Objective::Protect { tag } => {
+                    if !self
+                        .start_positions
+                        .iter()
+                        .any(|start| &start.entity_tag == tag)
+                    {
+                        return Err(CombatMapAssetLoaderError::UnknownCharacter(tag.clone()));
+                    }
+                }
+                Objective::Survive { .. } => {}
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Default)]
@@ -55,6 +154,19 @@ pub enum CombatMapAssetLoaderError {
     /// A [RON](ron) Error
     #[error("Could not parse RON: {0}")]
     RonSpannedError(#[from] ron::error::SpannedError),
+    /// A [ZoneMove] check that does not name a skill
+    #[error("Invalid check from {zone} to {to}: {error}")]
+    InvalidCheck {
+        zone: String,
+        to: String,
+        error: CheckSpecError,
+    },
+    /// An [Objective] in a zone that is not on the map
+    #[error("Objective in unknown zone {0}")]
+    UnknownZone(String),
+    /// An [Objective] about a character that is not on the map
+    #[error("Objective about unknown character {0}")]
+    UnknownCharacter(String),
 }
 
 impl AssetLoader for CombatMapAssetLoader {
@@ -71,6 +183,7 @@ impl AssetLoader for CombatMapAssetLoader {
             let mut bytes = Vec::new();
             reader.read_to_end(&mut bytes).await?;
             let custom_asset = ron::de::from_bytes::<CombatMap>(&bytes)?;
+            custom_asset.validate()?;
             Ok(custom_asset)
         })
     }
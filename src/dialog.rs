@@ -0,0 +1,351 @@
+use bevy::utils::thiserror;
+use bevy::{
+    asset::{io::Reader, ron, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    reflect::TypePath,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::characters::{ComplicationType, PlotHook};
+use crate::rules::checks::{CheckResult, CheckSpec, CheckSpecError};
+use crate::story::StoryTrigger;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DialogActor {
+    pub slug: String,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DialogAction {
+    #[default]
+    Say,
+    Join,
+    Leave,
+    /// The complication gets in the way of the actors of the node who have
+    /// it.
+    Complication(ComplicationType),
+    /// The plot hook comes up for the actors of the node who have it.
+    PlotHook(PlotHook),
+}
+
+impl DialogAction {
+    /// What the action earns story points for, if anything.
+    pub fn story_trigger(&self) -> Option<StoryTrigger> {
+        match self {
+            DialogAction::Complication(complication) => {
+                Some(StoryTrigger::Complication(*complication))
+            }
+            DialogAction::PlotHook(plot_hook) => Some(StoryTrigger::PlotHook(*plot_hook)),
+            DialogAction::Say | DialogAction::Join | DialogAction::Leave => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DialogChoice {
+    pub text: String,
+    pub next: usize,
+    /// A check to pass for the choice to work, e.g. `"agility"`.
+    #[serde(default)]
+    pub check: Option<String>,
+    /// Where to go when the check fails, staying put to choose again if unset.
+    #[serde(default)]
+    pub fail: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DialogNode {
+    pub id: usize,
+    #[serde(default)]
+    pub action: DialogAction,
+    #[serde(default)]
+    pub actors: Vec<String>,
+    #[serde(default)]
+    pub text: Option<String>,
+    /// The language the text is spoken in, understood by everyone if unset.
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub next: Option<usize>,
+    #[serde(default)]
+    pub choices: Option<Vec<DialogChoice>>,
+}
+
+impl DialogNode {
+    fn is_shown(&self) -> bool {
+        self.text.is_some() || self.choices.is_some()
+    }
+}
+
+#[derive(Asset, TypePath, Debug, Deserialize, Serialize, Clone)]
+pub struct DialogScript {
+    pub actors: Vec<DialogActor>,
+    pub script: Vec<DialogNode>,
+}
+
+impl DialogScript {
+    pub fn node(&self, id: usize) -> Option<&DialogNode> {
+        self.script.iter().find(|node| node.id == id)
+    }
+
+    pub fn actor(&self, slug: &str) -> Option<&DialogActor> {
+        self.actors.iter().find(|actor| actor.slug == slug)
+    }
+
+    /// Check that every `next` leads somewhere, every check names a skill
+    /// and no run of nodes without text loops back on itself.
+    pub fn validate(&self) -> Result<(), DialogError> {
+        for node in self.script.iter() {
+            self.skip_unshown(node.id, |_node| {})?;
+            let mut leads_to: Vec<usize> = node.next.into_iter().collect();
+            for choice in node.choices.iter().flatten() {
+                leads_to.push(choice.next);
+                leads_to.extend(choice.fail);
+                if let Some(check) = &choice.check {
+                    check.parse::<CheckSpec>()?;
+                }
+            }
+            if let Some(missing) = leads_to.into_iter().find(|id| self.node(*id).is_none()) {
+                return Err(DialogError::UnknownNode(missing));
+            }
+        }
+        Ok(())
+    }
+
+    /// Follow `next` from a node past those with nothing to show, handing
+    /// each node on the way to `visit`. Gives the node to show, if any.
+    fn skip_unshown(
+        &self,
+        id: usize,
+        mut visit: impl FnMut(&DialogNode),
+    ) -> Result<Option<usize>, DialogError> {
+        let mut visited = Vec::new();
+        let mut current = Some(id);
+        while let Some(node) = current.and_then(|id| self.node(id)) {
+            if visited.contains(&node.id) {
+                return Err(DialogError::Cycle(node.id));
+            }
+            visited.push(node.id);
+            visit(node);
+            if node.is_shown() {
+                return Ok(Some(node.id));
+            }
+            current = node.next;
+        }
+        Ok(None)
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, Error, PartialEq)]
+pub enum DialogError {
+    #[error("No node with id {0}")]
+    UnknownNode(usize),
+    #[error("No choice number {0}")]
+    UnknownChoice(usize),
+    #[error("Invalid check: {0}")]
+    InvalidCheck(#[from] CheckSpecError),
+    #[error("Node {0} loops back on itself without showing anything")]
+    Cycle(usize),
+}
+
+/// Walks a [`DialogScript`], keeping track of who is on stage.
+#[derive(Debug, Default, Clone)]
+pub struct DialogRunner {
+    current: Option<usize>,
+    on_stage: Vec<String>,
+    story_triggers: Vec<(String, StoryTrigger)>,
+}
+
+impl DialogRunner {
+    pub fn start(script: &DialogScript) -> Result<Self, DialogError> {
+        let mut runner = Self::default();
+        runner.go_to(script, script.script.first().map(|node| node.id))?;
+        Ok(runner)
+    }
+
+    /// The node being shown, `None` once the dialog is over.
+    pub fn node<'a>(&self, script: &'a DialogScript) -> Option<&'a DialogNode> {
+        self.current.and_then(|id| script.node(id))
+    }
+
+    pub fn on_stage(&self) -> &[String] {
+        &self.on_stage
+    }
+
+    /// The complications and plot hooks that came up since they were last
+    /// taken, with the actor they came up for.
+    pub fn story_triggers(&self) -> &[(String, StoryTrigger)] {
+        &self.story_triggers
+    }
+
+    pub fn take_story_triggers(&mut self) -> Vec<(String, StoryTrigger)> {
+        std::mem::take(&mut self.story_triggers)
+    }
+
+    /// The check one of the choices of the current node asks for, if any.
+    pub fn choice_check(
+        &self,
+        script: &DialogScript,
+        index: usize,
+    ) -> Result<Option<CheckSpec>, DialogError> {
+        let choice = self
+            .node(script)
+            .and_then(|node| node.choices.as_ref())
+            .and_then(|choices| choices.get(index))
+            .ok_or(DialogError::UnknownChoice(index))?;
+        Ok(choice
+            .check
+            .as_ref()
+            .map(|check| check.parse())
+            .transpose()?)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current.is_none()
+    }
+
+    /// Move on from a line of text.
+    pub fn proceed(&mut self, script: &DialogScript) -> Result<(), DialogError> {
+        let next = self.node(script).and_then(|node| node.next);
+        self.go_to(script, next)
+    }
+
+    /// Take one of the choices of the current node. The check of the choice,
+    /// if it has one, is rolled by `roll` and its result given back.
+    pub fn choose(
+        &mut self,
+        script: &DialogScript,
+        index: usize,
+        roll: impl FnOnce(&CheckSpec) -> CheckResult,
+    ) -> Result<Option<CheckResult>, DialogError> {
+        let choice = self
+            .node(script)
+            .and_then(|node| node.choices.as_ref())
+            .and_then(|choices| choices.get(index))
+            .ok_or(DialogError::UnknownChoice(index))?;
+        let Some(check) = &choice.check else {
+            self.go_to(script, Some(choice.next))?;
+            return Ok(None);
+        };
+        let result = roll(&check.parse()?);
+        if result.is_success() {
+            self.go_to(script, Some(choice.next))?;
+        } else if choice.fail.is_some() {
+            self.go_to(script, choice.fail)?;
+        }
+        Ok(Some(result))
+    }
+
+    /// Go to a node, carrying out the actions on the way until there
+    /// is something to show. Ends the dialog if the nodes on the way loop
+    /// back on themselves.
+    fn go_to(&mut self, script: &DialogScript, id: Option<usize>) -> Result<(), DialogError> {
+        self.current = None;
+        let Some(id) = id else {
+            return Ok(());
+        };
+        let mut triggers = Vec::new();
+        let on_stage = &mut self.on_stage;
+        self.current = script.skip_unshown(id, |node| {
+            match node.action {
+                DialogAction::Join => {
+                    for actor in node.actors.iter() {
+                        if !on_stage.contains(actor) {
+                            on_stage.push(actor.clone());
+                        }
+                    }
+                }
+                DialogAction::Leave => on_stage.retain(|actor| !node.actors.contains(actor)),
+                DialogAction::Say | DialogAction::Complication(_) | DialogAction::PlotHook(_) => {}
+            }
+            if let Some(trigger) = node.action.story_trigger() {
+                for actor in node.actors.iter() {
+                    triggers.push((actor.clone(), trigger));
+                }
+            }
+        })?;
+        self.story_triggers.extend(triggers);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct DialogAssetLoader;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum DialogAssetLoaderError {
+    /// An [IO](std::io) Error
+    #[error("Could not load asset: {0}")]
+    Io(#[from] std::io::Error),
+    /// A [RON](ron) Error
+    #[error("Could not parse RON: {0}")]
+    RonSpannedError(#[from] ron::error::SpannedError),
+    /// A script that leads nowhere
+    #[error("Invalid dialog: {0}")]
+    Dialog(#[from] DialogError),
+}
+
+impl AssetLoader for DialogAssetLoader {
+    type Asset = DialogScript;
+    type Settings = ();
+    type Error = DialogAssetLoaderError;
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let custom_asset = ron::de::from_bytes::<DialogScript>(&bytes)?;
+            custom_asset.validate()?;
+            Ok(custom_asset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["talk.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(nodes: &str) -> DialogScript {
+        ron::from_str(&format!("(actors: [], script: [{}])", nodes)).unwrap()
+    }
+
+    #[test]
+    fn a_loop_of_nodes_without_text_is_rejected() {
+        let script = script(
+            r#"(id: 0, text: Some("Hello"), next: Some(1)),
+               (id: 1, action: Join, next: Some(2)),
+               (id: 2, action: Leave, next: Some(1)),"#,
+        );
+        assert_eq!(script.validate(), Err(DialogError::Cycle(1)));
+
+        let mut runner = DialogRunner::start(&script).unwrap();
+        assert_eq!(runner.proceed(&script), Err(DialogError::Cycle(1)));
+        assert!(runner.is_finished());
+    }
+
+    #[test]
+    fn a_loop_that_shows_a_line_is_fine() {
+        let script = script(
+            r#"(id: 0, text: Some("Again?"), next: Some(1)),
+               (id: 1, action: Join, next: Some(0)),"#,
+        );
+        assert_eq!(script.validate(), Ok(()));
+
+        let mut runner = DialogRunner::start(&script).unwrap();
+        runner.proceed(&script).unwrap();
+        assert_eq!(runner.node(&script).map(|node| node.id), Some(0));
+    }
+}
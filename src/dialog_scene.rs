@@ -0,0 +1,644 @@
+use bevy::prelude::*;
+
+use crate::asset_loader::CombatAsset;
+use crate::campaign::Scene;
+use crate::characters::{GearKitType, SaveCharacters};
+use crate::dialog::{DialogRunner, DialogScript};
+use crate::dice::SeededDice;
+use crate::language::{self, Comprehension};
+use crate::rules::checks::{CheckResult, CheckSpec, SkillCheck};
+use crate::rules::flips::{StorySpend, SIGNATURE_GEAR_BONUS};
+use crate::rules::DEFAULT_SKILL;
+use crate::scenes::{CurrentScene, NextScene};
+use crate::states::GameState;
+use crate::story::StoryPoints;
+use crate::utils::despawn_screen;
+use crate::TEXT_COLOR;
+
+pub struct DialogScene;
+
+impl Plugin for DialogScene {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Dialog), dialog_setup)
+            .add_systems(
+                Update,
+                (
+                    advance_dialog,
+                    choose_dialog_option,
+                    answer_story_offer,
+                    award_story_points,
+                    hear_line,
+                    show_dialog_node,
+                    show_choice_hover,
+                    bevy::window::close_on_esc,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Dialog)),
+            )
+            .add_systems(OnExit(GameState::Dialog), despawn_screen::<OnDialogScreen>);
+    }
+}
+
+#[derive(Component)]
+struct OnDialogScreen;
+
+#[derive(Component)]
+struct SpeakerPortrait;
+
+#[derive(Component)]
+struct SpeakerName;
+
+#[derive(Component)]
+struct DialogText;
+
+#[derive(Component)]
+struct ChoiceList;
+
+#[derive(Component)]
+struct ChoiceButton(usize);
+
+/// Spends a story point on the check on offer.
+#[derive(Component)]
+struct SpendButton(StorySpend);
+
+/// Takes the check on offer as it was rolled.
+#[derive(Component)]
+struct AcceptButton;
+
+/// The dialog being played and how far along it is.
+#[derive(Resource, Debug)]
+struct ActiveDialog {
+    script: Handle<DialogScript>,
+    runner: DialogRunner,
+    /// A failed check the player character who rolled it can spend a story
+    /// point on, held back until they decide.
+    story_offer: Option<StoryOffer>,
+    /// The text of the node being shown as the listeners follow it, by node
+    /// id.
+    heard: Option<(usize, String)>,
+}
+
+#[derive(Debug)]
+struct StoryOffer {
+    choice: usize,
+    roller: String,
+    skill_check: SkillCheck,
+    check: CheckResult,
+    /// The roller's signature gear, which a story point can bring in.
+    signature_gear: Option<GearKitType>,
+}
+
+const CHOICE_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+const HOVERED_CHOICE_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
+
+/// Who speaks when a line has no actor.
+const NARRATOR: &str = "narrator";
+
+#[allow(clippy::too_many_arguments)]
+fn dialog_setup(
+    mut commands: Commands,
+    combat_asset: Res<CombatAsset>,
+    current_scene: Res<CurrentScene>,
+    scripts: Res<Assets<DialogScript>>,
+    asset_server: Res<AssetServer>,
+    mut next_scene: ResMut<NextScene>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    let Scene::Dialog { script: path, .. } = &current_scene.scene else {
+        return;
+    };
+    let Some((handle, script)) = combat_asset
+        .dialogs
+        .get(path)
+        .and_then(|handle| scripts.get(handle).map(|script| (handle, script)))
+    else {
+        // Skip the scene rather than play on with the dialog before it.
+        warn!("dialog not loaded: {:?}", path);
+        commands.insert_resource(ActiveDialog {
+            script: Handle::default(),
+            runner: DialogRunner::default(),
+            story_offer: None,
+            heard: None,
+        });
+        current_scene.finish(None, &mut next_scene, &mut game_state);
+        return;
+    };
+    // A dialog that cannot start is over at once.
+    let runner = DialogRunner::start(script).unwrap_or_else(|error| {
+        warn!("dialog {:?}: {}", path, error);
+        DialogRunner::default()
+    });
+    commands.insert_resource(ActiveDialog {
+        script: handle.clone(),
+        runner,
+        story_offer: None,
+        heard: None,
+    });
+
+    let font_handle = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::FlexStart,
+                    justify_content: JustifyContent::Center,
+                    padding: UiRect::all(Val::Px(40.0)),
+                    column_gap: Val::Px(40.0),
+                    ..default()
+                },
+                background_color: Color::BLACK.into(),
+                ..default()
+            },
+            OnDialogScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        AtlasImageBundle {
+                            style: Style {
+                                width: Val::Px(200.0),
+                                ..default()
+                            },
+                            texture_atlas: TextureAtlas {
+                                layout: combat_asset.portrait_atlas.clone(),
+                                index: combat_asset
+                                    .portraits
+                                    .get(NARRATOR)
+                                    .copied()
+                                    .unwrap_or_default(),
+                            },
+                            image: UiImage::new(combat_asset.portrait_image.clone()),
+                            ..default()
+                        },
+                        SpeakerPortrait,
+                    ));
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: font_handle.clone(),
+                                font_size: 24.0,
+                                color: TEXT_COLOR,
+                            },
+                        ),
+                        SpeakerName,
+                    ));
+                });
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Percent(60.0),
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: font_handle.clone(),
+                                font_size: 22.0,
+                                color: TEXT_COLOR,
+                            },
+                        ),
+                        DialogText,
+                    ));
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Column,
+                                row_gap: Val::Px(10.0),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        ChoiceList,
+                    ));
+                });
+        });
+}
+
+fn advance_dialog(
+    mut active_dialog: ResMut<ActiveDialog>,
+    scripts: Res<Assets<DialogScript>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    current_scene: Res<CurrentScene>,
+    mut next_scene: ResMut<NextScene>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    let Some(script) = scripts.get(&active_dialog.script) else {
+        return;
+    };
+    let has_choices = active_dialog
+        .runner
+        .node(script)
+        .map(|node| node.choices.is_some())
+        .unwrap_or(false);
+    if !has_choices
+        && (keys.any_just_pressed([KeyCode::Space, KeyCode::Enter])
+            || mouse_buttons.just_pressed(MouseButton::Left))
+    {
+        if let Err(error) = active_dialog.runner.proceed(script) {
+            warn!("dialog: {}", error);
+        }
+    }
+    if active_dialog.runner.is_finished() {
+        current_scene.finish(None, &mut next_scene, &mut game_state);
+    }
+}
+
+fn choose_dialog_option(
+    mut active_dialog: ResMut<ActiveDialog>,
+    scripts: Res<Assets<DialogScript>>,
+    combat_asset: Res<CombatAsset>,
+    saved_characters: Res<Assets<SaveCharacters>>,
+    story_points: Res<StoryPoints>,
+    mut dice: ResMut<SeededDice>,
+    choice_buttons: Query<(&Interaction, &ChoiceButton), Changed<Interaction>>,
+) {
+    let Some(script) = scripts.get(&active_dialog.script) else {
+        return;
+    };
+    let Some((_interaction, choice)) = choice_buttons
+        .iter()
+        .find(|(interaction, _choice)| **interaction == Interaction::Pressed)
+    else {
+        return;
+    };
+    let spec = match active_dialog.runner.choice_check(script, choice.0) {
+        Ok(spec) => spec,
+        Err(error) => {
+            warn!("dialog choice {}: {}", choice.0, error);
+            return;
+        }
+    };
+    let Some(spec) = spec else {
+        // Without a check there is nothing to roll.
+        take_choice(&mut active_dialog, script, choice.0, |spec| {
+            spec.against(DEFAULT_SKILL).roll(&mut *dice)
+        });
+        return;
+    };
+    // The best suited character on stage takes the check.
+    let roller = saved_characters
+        .get(&combat_asset.characters)
+        .and_then(|chars| {
+            chars
+                .player_characters
+                .iter()
+                .filter(|pc| active_dialog.runner.on_stage().contains(&pc.tag))
+                .max_by_key(|pc| pc.get_skill(spec.skill))
+        });
+    let skill_check = spec.against(
+        roller
+            .map(|pc| pc.get_skill(spec.skill))
+            .unwrap_or(DEFAULT_SKILL),
+    );
+    let check = skill_check.roll(&mut *dice);
+    match roller {
+        Some(pc) if !check.is_success() && story_points.get(&pc.tag) > 0 => {
+            active_dialog.story_offer = Some(StoryOffer {
+                choice: choice.0,
+                roller: pc.tag.clone(),
+                skill_check,
+                check,
+                signature_gear: pc
+                    .gear_kits
+                    .iter()
+                    .find(|kit| kit.is_signature())
+                    .map(|kit| kit.kit_type()),
+            });
+        }
+        _ => take_choice(&mut active_dialog, script, choice.0, |_spec| check),
+    }
+}
+
+/// Settle the check on offer, spending a story point on it or not.
+fn answer_story_offer(
+    mut active_dialog: ResMut<ActiveDialog>,
+    scripts: Res<Assets<DialogScript>>,
+    mut story_points: ResMut<StoryPoints>,
+    mut dice: ResMut<SeededDice>,
+    spend_buttons: Query<(&Interaction, &SpendButton), Changed<Interaction>>,
+    accept_buttons: Query<&Interaction, (With<AcceptButton>, Changed<Interaction>)>,
+) {
+    let pressed = |interaction: &Interaction| *interaction == Interaction::Pressed;
+    let spend = if let Some((_interaction, spend)) = spend_buttons
+        .iter()
+        .find(|(interaction, _spend)| pressed(interaction))
+    {
+        Some(spend.0)
+    } else if accept_buttons.iter().any(pressed) {
+        None
+    } else {
+        return;
+    };
+    let Some(script) = scripts.get(&active_dialog.script) else {
+        return;
+    };
+    let Some(offer) = active_dialog.story_offer.take() else {
+        return;
+    };
+    let check = match spend {
+        Some(spend) if story_points.spend(&offer.roller) => {
+            let check = spend.apply(offer.skill_check, offer.check, &mut *dice);
+            info!(
+                "{} spends a story point on {:?}: {} to {}",
+                offer.roller,
+                spend,
+                offer.check.roll.value(),
+                check.roll.value()
+            );
+            check
+        }
+        _ => offer.check,
+    };
+    take_choice(&mut active_dialog, script, offer.choice, |_spec| check);
+}
+
+fn take_choice(
+    active_dialog: &mut ActiveDialog,
+    script: &DialogScript,
+    index: usize,
+    roll: impl FnOnce(&CheckSpec) -> CheckResult,
+) {
+    let result = active_dialog.runner.choose(script, index, roll);
+    match result {
+        Ok(Some(check)) => debug!("dialog check: {:?}", check),
+        Ok(None) => {}
+        Err(error) => warn!("dialog choice {}: {}", index, error),
+    }
+}
+
+/// Give story points to the player characters whose complications or plot
+/// hooks came up.
+fn award_story_points(
+    mut active_dialog: ResMut<ActiveDialog>,
+    combat_asset: Res<CombatAsset>,
+    saved_characters: Res<Assets<SaveCharacters>>,
+    mut story_points: ResMut<StoryPoints>,
+) {
+    if active_dialog.runner.story_triggers().is_empty() {
+        return;
+    }
+    let Some(saved_chars) = saved_characters.get(&combat_asset.characters) else {
+        return;
+    };
+    for (actor, trigger) in active_dialog.runner.take_story_triggers() {
+        let has_it = saved_chars
+            .player_characters
+            .iter()
+            .any(|pc| pc.tag == actor && trigger.applies_to(pc));
+        if has_it {
+            story_points.award(&actor, trigger.award());
+            info!(
+                "{} earns {} story point for {}",
+                actor,
+                trigger.award(),
+                trigger
+            );
+        }
+    }
+}
+
+/// Work out how the player characters listening follow the line of the node
+/// once it comes up, rolling comprehension checks for those still learning
+/// its language.
+fn hear_line(
+    mut active_dialog: ResMut<ActiveDialog>,
+    scripts: Res<Assets<DialogScript>>,
+    combat_asset: Res<CombatAsset>,
+    saved_characters: Res<Assets<SaveCharacters>>,
+    mut dice: ResMut<SeededDice>,
+) {
+    let Some(script) = scripts.get(&active_dialog.script) else {
+        return;
+    };
+    let Some(node) = active_dialog.runner.node(script) else {
+        return;
+    };
+    if active_dialog
+        .heard
+        .as_ref()
+        .is_some_and(|(id, _text)| *id == node.id)
+    {
+        return;
+    }
+    let text = node.text.clone().unwrap_or_default();
+    let (Some(language), Some(saved_chars)) = (
+        &node.language,
+        saved_characters.get(&combat_asset.characters),
+    ) else {
+        active_dialog.heard = Some((node.id, text));
+        return;
+    };
+    // Everyone on stage listens, apart from the speakers.
+    let listeners: Vec<(String, Comprehension)> = saved_chars
+        .player_characters
+        .iter()
+        .filter(|pc| {
+            active_dialog.runner.on_stage().contains(&pc.tag) && !node.actors.contains(&pc.tag)
+        })
+        .map(|pc| {
+            let (comprehension, check) = language::comprehend(pc, language, &mut *dice);
+            if let Some(check) = check {
+                debug!("{} follows {}: {:?}", pc.tag, language, check);
+            }
+            let name = script
+                .actor(&pc.tag)
+                .map(|actor| actor.name.clone())
+                .unwrap_or(pc.tag.clone());
+            (name, comprehension)
+        })
+        .collect();
+    if listeners.is_empty() {
+        active_dialog.heard = Some((node.id, text));
+        return;
+    }
+    // Each listener hears the line as far as they follow it.
+    let heard: Vec<String> = listeners
+        .iter()
+        .map(|(name, comprehension)| match comprehension {
+            Comprehension::Understood => format!("{} follows the {}:\n{}", name, language, text),
+            Comprehension::Garbled { learning } => format!(
+                "{} partly follows the {}:\n{}",
+                name,
+                language,
+                language::garble(&text, *learning)
+            ),
+            Comprehension::Foreign => {
+                format!(
+                    "{} hears something in {} they do not follow.",
+                    name, language
+                )
+            }
+        })
+        .collect();
+    let heard = heard.join("\n\n");
+    active_dialog.heard = Some((node.id, heard));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn show_dialog_node(
+    mut commands: Commands,
+    active_dialog: Res<ActiveDialog>,
+    scripts: Res<Assets<DialogScript>>,
+    combat_asset: Res<CombatAsset>,
+    asset_server: Res<AssetServer>,
+    story_points: Res<StoryPoints>,
+    mut portrait: Query<&mut TextureAtlas, With<SpeakerPortrait>>,
+    mut speaker_name: Query<&mut Text, (With<SpeakerName>, Without<DialogText>)>,
+    mut dialog_text: Query<&mut Text, (With<DialogText>, Without<SpeakerName>)>,
+    choice_list: Query<Entity, With<ChoiceList>>,
+) {
+    if !active_dialog.is_changed() {
+        return;
+    }
+    let Some(script) = scripts.get(&active_dialog.script) else {
+        return;
+    };
+    let Some(node) = active_dialog.runner.node(script) else {
+        return;
+    };
+
+    let speaker = node.actors.first().map(String::as_str);
+    if let Ok(mut atlas) = portrait.get_single_mut() {
+        if let Some(index) = combat_asset.portraits.get(speaker.unwrap_or(NARRATOR)) {
+            atlas.index = *index;
+        }
+    }
+    if let Ok(mut text) = speaker_name.get_single_mut() {
+        text.sections[0].value = speaker
+            .map(|slug| {
+                script
+                    .actor(slug)
+                    .map(|actor| actor.name.clone())
+                    .unwrap_or(slug.to_string())
+            })
+            .unwrap_or_default();
+    }
+    if let Ok(mut text) = dialog_text.get_single_mut() {
+        text.sections[0].value = match &active_dialog.heard {
+            Some((id, heard)) if *id == node.id => heard.clone(),
+            _ => node.text.clone().unwrap_or_default(),
+        };
+        if let Some(trigger) = node.action.story_trigger() {
+            text.sections[0].value.push_str(&format!(
+                "\n\n({}: +{} story point)",
+                trigger,
+                trigger.award()
+            ));
+        }
+    }
+
+    let Ok(choice_list_entity) = choice_list.get_single() else {
+        return;
+    };
+    let font_handle = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .entity(choice_list_entity)
+        .despawn_descendants()
+        .with_children(|parent| {
+            // A failed check on offer takes the place of the choices.
+            if let Some(offer) = &active_dialog.story_offer {
+                parent.spawn(TextBundle::from_section(
+                    format!(
+                        "{} rolled {} against {}. Spend a story point? ({} left)",
+                        offer.roller,
+                        offer.check.roll.value(),
+                        offer.check.target,
+                        story_points.get(&offer.roller)
+                    ),
+                    TextStyle {
+                        font: font_handle.clone(),
+                        font_size: 20.0,
+                        color: TEXT_COLOR,
+                    },
+                ));
+                let flipped = offer.check.flipped();
+                if flipped.is_success() {
+                    let text = format!("Flip it to {}", flipped.roll.value());
+                    add_choice_button(parent, SpendButton(StorySpend::Flip), text, &font_handle);
+                }
+                let text = "Roll again".to_string();
+                add_choice_button(parent, SpendButton(StorySpend::Reroll), text, &font_handle);
+                if let Some(gear) = offer.signature_gear {
+                    let text = format!("Bring in the {:?} (+{})", gear, SIGNATURE_GEAR_BONUS);
+                    let marker = SpendButton(StorySpend::SignatureGear);
+                    add_choice_button(parent, marker, text, &font_handle);
+                }
+                let text = "Take the roll".to_string();
+                add_choice_button(parent, AcceptButton, text, &font_handle);
+                return;
+            }
+            for (index, choice) in node.choices.iter().flatten().enumerate() {
+                add_choice_button(
+                    parent,
+                    ChoiceButton(index),
+                    choice.text.clone(),
+                    &font_handle,
+                );
+            }
+        });
+}
+
+fn add_choice_button(
+    parent: &mut ChildBuilder,
+    marker: impl Component,
+    text: String,
+    font_handle: &Handle<Font>,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::all(Val::Px(10.0)),
+                    border: UiRect::all(Val::Px(2.0)),
+                    ..default()
+                },
+                border_color: BorderColor::from(Color::BLACK),
+                background_color: BackgroundColor::from(CHOICE_BUTTON),
+                ..default()
+            },
+            marker,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                text,
+                TextStyle {
+                    font: font_handle.clone(),
+                    font_size: 20.0,
+                    color: TEXT_COLOR,
+                },
+            ));
+        });
+}
+
+fn show_choice_hover(
+    mut choice_buttons: Query<
+        (&Interaction, &mut BackgroundColor),
+        (With<Button>, Changed<Interaction>),
+    >,
+) {
+    for (interaction, mut color) in choice_buttons.iter_mut() {
+        *color = match interaction {
+            Interaction::Hovered | Interaction::Pressed => HOVERED_CHOICE_BUTTON.into(),
+            Interaction::None => CHOICE_BUTTON.into(),
+        };
+    }
+}
@@ -0,0 +1,141 @@
+//! Dice rolling behind the [`Dice`] trait, so rolls can be seeded and replayed.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::Resource;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// A d100 roll, read as a tens and a ones die.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct D100(pub u8);
+
+impl D100 {
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+
+    pub fn tens(&self) -> u8 {
+        self.0 / 10
+    }
+
+    pub fn ones(&self) -> u8 {
+        self.0 % 10
+    }
+
+    /// Both dice show the same number, which makes the roll a critical. A
+    /// roll of 100 shows 00 on the dice and counts as well.
+    pub fn is_double(&self) -> bool {
+        self.0 == 100 || self.tens() == self.ones()
+    }
+
+    /// The roll read the other way round, with the tens and ones swapped.
+    pub fn flipped(&self) -> D100 {
+        if self.0 == 100 {
+            return *self;
+        }
+        D100(self.ones() * 10 + self.tens())
+    }
+}
+
+pub trait Dice {
+    fn d100(&mut self) -> D100;
+    fn d10(&mut self) -> u8;
+    fn d6(&mut self) -> u8;
+}
+
+/// The dice used by the game, seeded so a session can be replayed.
+#[derive(Resource, Debug, Clone)]
+pub struct SeededDice {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl SeededDice {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn from_entropy() -> Self {
+        Self::new(rand::random())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Default for SeededDice {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
+
+impl Dice for SeededDice {
+    fn d100(&mut self) -> D100 {
+        D100(self.rng.gen_range(1..=100))
+    }
+
+    fn d10(&mut self) -> u8 {
+        self.rng.gen_range(1..=10)
+    }
+
+    fn d6(&mut self) -> u8 {
+        self.rng.gen_range(1..=6)
+    }
+}
+
+/// Dice that give back the given rolls in order, starting over when they run out.
+#[derive(Debug, Default)]
+pub struct LoadedDice {
+    rolls: VecDeque<u8>,
+}
+
+impl LoadedDice {
+    pub fn new(rolls: &[u8]) -> Self {
+        Self {
+            rolls: rolls.iter().copied().collect(),
+        }
+    }
+
+    fn next(&mut self) -> u8 {
+        let Some(roll) = self.rolls.pop_front() else {
+            return 1;
+        };
+        self.rolls.push_back(roll);
+        roll
+    }
+}
+
+impl Dice for LoadedDice {
+    fn d100(&mut self) -> D100 {
+        D100(self.next())
+    }
+
+    fn d10(&mut self) -> u8 {
+        self.next()
+    }
+
+    fn d6(&mut self) -> u8 {
+        self.next()
+    }
+}
+
+/// A seed asked for on the command line with `--seed <number>`.
+#[derive(Resource, Debug, Default)]
+pub struct DiceSeed(pub Option<u64>);
+
+impl DiceSeed {
+    pub fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        Self(
+            args.iter()
+                .position(|arg| arg == "--seed")
+                .and_then(|index| args.get(index + 1))
+                .and_then(|seed| seed.parse().ok()),
+        )
+    }
+}
@@ -0,0 +1,180 @@
+//! Lines of dialog spoken in some language, followed by each listener as far
+//! as their command of it goes.
+//!
+//! Native and fluent speakers follow a line outright. Someone still learning
+//! the language rolls Languages, easier the further along they are, and only
+//! catches part of the line when the check fails. Everyone else hears a
+//! foreign language.
+
+use crate::characters::{LanguageLevel, SavePlayerCharacter, SkillKey};
+use crate::dice::Dice;
+use crate::rules::checks::{CheckResult, SkillCheck};
+
+/// What each step of learning adds to the Languages check, `Learning2`
+/// rolling without a modifier.
+pub const LEARNING_STEP: i16 = 10;
+
+/// Stands in for the words of a line that were missed.
+pub const MISSED_WORDS: &str = "…";
+
+/// How much of a line a listener follows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Comprehension {
+    /// Does not speak the language.
+    Foreign,
+    /// Catches some of the words, the more the further along in learning.
+    Garbled { learning: u8 },
+    /// Follows every word.
+    Understood,
+}
+
+impl LanguageLevel {
+    /// How far along in learning the language, from 0 to 4, `None` for
+    /// native and fluent speakers.
+    pub fn learning(&self) -> Option<u8> {
+        match self {
+            LanguageLevel::Native | LanguageLevel::Fluent => None,
+            LanguageLevel::Learning4 => Some(4),
+            LanguageLevel::Learning3 => Some(3),
+            LanguageLevel::Learning2 => Some(2),
+            LanguageLevel::Learning1 => Some(1),
+            LanguageLevel::Learning0 => Some(0),
+        }
+    }
+
+    /// The check a learner with the given Languages skill rolls to follow a
+    /// line, `None` for those who need none.
+    pub fn comprehension_check(&self, languages: u8) -> Option<SkillCheck> {
+        self.learning().map(|learning| {
+            SkillCheck::new(languages).with_modifier((learning as i16 - 2) * LEARNING_STEP)
+        })
+    }
+}
+
+/// How well a player character follows a line in the language, with the
+/// check rolled for it if they are still learning it.
+pub fn comprehend(
+    pc: &SavePlayerCharacter,
+    language: &str,
+    dice: &mut impl Dice,
+) -> (Comprehension, Option<CheckResult>) {
+    let Some(level) = pc.language_level(language) else {
+        return (Comprehension::Foreign, None);
+    };
+    let Some(skill_check) = level.comprehension_check(pc.get_skill(SkillKey::Languages)) else {
+        return (Comprehension::Understood, None);
+    };
+    let check = skill_check.roll(dice);
+    let comprehension = if check.is_success() {
+        Comprehension::Understood
+    } else {
+        Comprehension::Garbled {
+            learning: level.learning().unwrap_or_default(),
+        }
+    };
+    (comprehension, Some(check))
+}
+
+/// The line as caught by a learner, with the words they missed left out.
+/// Each step of learning catches another fifth of the words.
+pub fn garble(text: &str, learning: u8) -> String {
+    let mut words: Vec<&str> = text
+        .split_whitespace()
+        .enumerate()
+        .map(|(index, word)| {
+            if index * 3 % 5 < learning as usize {
+                word
+            } else {
+                MISSED_WORDS
+            }
+        })
+        .collect();
+    words.dedup_by(|word, previous| *word == MISSED_WORDS && *previous == MISSED_WORDS);
+    words.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::ron;
+
+    use super::*;
+    use crate::dice::LoadedDice;
+
+    /// Paul, native in French and learning Japanese, with Languages 50.
+    fn paul() -> SavePlayerCharacter {
+        ron::from_str(
+            r#"(
+                tag: "paul",
+                name: (first: "Paul", last: "Marchand", alias: "Paul"),
+                profession: "Cat Burglar",
+                skills: [Languages(50)],
+                vitality: 6,
+                abilities: [],
+                complications: [],
+                languages: [
+                    (name: "French", level: Native),
+                    (name: "Japanese", level: Learning1),
+                ],
+                plot_hooks: [],
+                gear_kits: [],
+            )"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn native_speakers_always_follow_and_others_hear_a_foreign_language() {
+        let mut dice = LoadedDice::new(&[99]);
+        assert_eq!(
+            comprehend(&paul(), "French", &mut dice),
+            (Comprehension::Understood, None)
+        );
+        assert_eq!(
+            comprehend(&paul(), "Swedish", &mut dice),
+            (Comprehension::Foreign, None)
+        );
+    }
+
+    #[test]
+    fn learners_roll_languages_easier_the_further_along_they_are() {
+        for (level, target) in [
+            (LanguageLevel::Learning0, 30),
+            (LanguageLevel::Learning1, 40),
+            (LanguageLevel::Learning2, 50),
+            (LanguageLevel::Learning3, 60),
+            (LanguageLevel::Learning4, 70),
+        ] {
+            let check = level.comprehension_check(50).unwrap();
+            assert_eq!(check.target(), target, "{:?}", level);
+        }
+        assert_eq!(LanguageLevel::Fluent.comprehension_check(50), None);
+
+        // Learning1 rolls against 40, catching only part of the line on a miss.
+        let mut dice = LoadedDice::new(&[45, 35]);
+        let (comprehension, check) = comprehend(&paul(), "Japanese", &mut dice);
+        assert_eq!(comprehension, Comprehension::Garbled { learning: 1 });
+        assert_eq!(check.map(|check| check.target), Some(40));
+        let (comprehension, _check) = comprehend(&paul(), "Japanese", &mut dice);
+        assert_eq!(comprehension, Comprehension::Understood);
+    }
+
+    #[test]
+    fn more_of_a_line_is_caught_the_further_along_in_learning() {
+        let line = "whoever locked us in here has never met a rokudan judoka";
+        let caught = |learning| {
+            garble(line, learning)
+                .split_whitespace()
+                .filter(|word| *word != MISSED_WORDS)
+                .count()
+        };
+        assert_eq!(garble(line, 0), MISSED_WORDS);
+        for learning in 0..4 {
+            assert!(
+                caught(learning) < caught(learning + 1),
+                "learning {}",
+                learning
+            );
+        }
+        assert!(caught(4) < line.split_whitespace().count());
+    }
+}
@@ -1,2 +1,8 @@
+pub mod campaign;
 pub mod characters;
 pub mod combat_map;
+pub mod dialog;
+pub mod dice;
+pub mod language;
+pub mod rules;
+pub mod story;
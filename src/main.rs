@@ -2,12 +2,13 @@
 
 use bevy::log::LogPlugin;
 use bevy::prelude::*;
+use game_test::{campaign, characters, combat_map, dialog, dice, language, rules, story};
 
 mod asset_loader;
-mod characters;
 mod combat;
-mod combat_map;
+mod dialog_scene;
 mod end_scene;
+mod scenes;
 mod schedule;
 mod states;
 mod utils;
@@ -25,8 +26,16 @@ fn main() {
             ..default()
         }),))
         .init_state::<states::GameState>()
+        .insert_resource(dice::DiceSeed::from_args())
+        .insert_resource(story::NewGame::from_args())
         .add_systems(Startup, setup)
-        .add_plugins((asset_loader::AssetLoader, end_scene::TheEnd, combat::Combat))
+        .add_plugins((
+            asset_loader::AssetLoader,
+            dialog_scene::DialogScene,
+            end_scene::TheEnd,
+            scenes::Scenes,
+            combat::Combat,
+        ))
         .run();
 }
 
@@ -0,0 +1,1911 @@
+//! The combat rules, kept apart from any Bevy systems.
+//!
+//! A [`CombatState`] is advanced by applying [`Action`]s to it, each of which
+//! gives back the new state together with the [`CombatEvent`]s that happened.
+//! The combat plugin drives the same core and turns the events into changes on
+//! screen, which lets encounters be resolved without opening a window.
+
+use std::collections::{HashMap, VecDeque};
+
+use bevy::utils::thiserror;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::characters::{
+    AbilityType, Attack, AttackTag, CharacterSkills, ComplicationType, DCTag, SkillKey,
+};
+use crate::combat_map::{CombatMap, Objective, ZoneMove};
+use crate::dice::{Dice, D100};
+
+pub mod abilities;
+pub mod checks;
+pub mod complications;
+pub mod director;
+pub mod effects;
+pub mod flips;
+
+use abilities::AbilityHooks;
+use checks::{CheckOutcome, CheckResult, CheckSpec, SkillCheck};
+use effects::{StatusEffect, StatusKind, PARALYSED_ROUNDS};
+use flips::{Flipper, StorySpend, COMPLICATION_AWARD, PLAYER_FLIPS};
+
+/// The skill value of anyone without the skill.
+pub const DEFAULT_SKILL: u8 = 15;
+
+/// The check modifier for attacking or moving while prone.
+pub const PRONE_MODIFIER: i16 = -20;
+
+/// The check bonus a squad gets on its attacks for each member past the
+/// first.
+pub const SQUAD_BONUS: i16 = 10;
+
+/// The most a squad's attacks gain from its numbers, however many it has.
+pub const SQUAD_MAX_BONUS: i16 = 20;
+
+/// How far apart two zones are, counted in moves between them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RangeBand {
+    Same,
+    Adjacent,
+    Far,
+}
+
+impl RangeBand {
+    pub fn from_moves(moves: usize) -> Self {
+        match moves {
+            0 => RangeBand::Same,
+            1 => RangeBand::Adjacent,
+            _ => RangeBand::Far,
+        }
+    }
+}
+
+/// The check modifier for making an attack at a range, `None` when it does
+/// not reach that far. Melee needs a shared zone, short range weapons lose
+/// accuracy in the next zone and reach no further, and other weapons shoot
+/// worse at far targets.
+pub fn range_modifier(attack: &Attack, band: RangeBand) -> Option<i16> {
+    let melee = attack.tags.contains(&AttackTag::Melee);
+    let short_range = attack.tags.contains(&AttackTag::ShortRange);
+    match band {
+        RangeBand::Same => Some(0),
+        RangeBand::Adjacent if melee => None,
+        RangeBand::Adjacent if short_range => Some(-10),
+        RangeBand::Adjacent => Some(0),
+        RangeBand::Far if melee || short_range => None,
+        RangeBand::Far => Some(-10),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CombatantId(pub u64);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Side {
+    Players,
+    Director,
+}
+
+#[derive(Clone, Debug)]
+pub struct Combatant {
+    pub id: CombatantId,
+    /// The tag of the character in the map's start positions.
+    pub slug: String,
+    pub side: Side,
+    pub initiative: u8,
+    pub vitality: u8,
+    pub zone: String,
+    /// Lying on the ground, until getting up.
+    pub prone: bool,
+    pub attacks: Vec<Attack>,
+    /// The attack in hand, as an index into `attacks`.
+    pub equipped: usize,
+    /// Shots left for each of the `attacks`, `None` for those that need no
+    /// reloading.
+    pub ammo: Vec<Option<u8>>,
+    pub skills: CharacterSkills,
+    pub tags: Vec<DCTag>,
+    /// Flips left to spend, for director characters.
+    pub flips: u8,
+    /// Timed effects on the combatant, at most one of each kind.
+    pub statuses: Vec<StatusEffect>,
+    /// Members still standing, for mooks fighting as a squad. Any hit takes
+    /// one of them down.
+    pub squad: Option<u8>,
+    pub abilities: Vec<AbilityType>,
+    pub complications: Vec<ComplicationType>,
+    /// Story points left to spend on checks, for player characters.
+    pub story_points: u8,
+}
+
+impl Combatant {
+    pub fn new(id: CombatantId, side: Side, initiative: u8, vitality: u8, zone: &str) -> Self {
+        Self {
+            id,
+            slug: String::new(),
+            side,
+            initiative,
+            vitality,
+            zone: zone.to_string(),
+            prone: false,
+            attacks: Vec::new(),
+            equipped: 0,
+            ammo: Vec::new(),
+            skills: CharacterSkills::default(),
+            tags: Vec::new(),
+            flips: 0,
+            statuses: Vec::new(),
+            squad: None,
+            abilities: Vec::new(),
+            complications: Vec::new(),
+            story_points: 0,
+        }
+    }
+
+    pub fn with_slug(mut self, slug: &str) -> Self {
+        self.slug = slug.to_string();
+        self
+    }
+
+    /// Give the combatant its attacks, all of them loaded.
+    pub fn with_attacks(mut self, attacks: Vec<Attack>) -> Self {
+        self.ammo = attacks.iter().map(|attack| attack.capacity()).collect();
+        self.attacks = attacks;
+        self
+    }
+
+    pub fn with_skills(mut self, skills: CharacterSkills) -> Self {
+        self.skills = skills;
+        self
+    }
+
+    /// Give the combatant its tags, with the flips they come with.
+    pub fn with_tags(mut self, tags: Vec<DCTag>) -> Self {
+        self.flips = tags
+            .iter()
+            .map(|tag| match tag {
+                DCTag::Flips(flips) => *flips,
+                _ => 0,
+            })
+            .sum();
+        self.tags = tags;
+        self
+    }
+
+    /// Make the combatant a squad of that many mooks.
+    pub fn with_squad(mut self, members: u8) -> Self {
+        self.squad = Some(members);
+        self
+    }
+
+    pub fn with_abilities(mut self, abilities: Vec<AbilityType>) -> Self {
+        self.abilities = abilities;
+        self
+    }
+
+    pub fn with_complications(mut self, complications: Vec<ComplicationType>) -> Self {
+        self.complications = complications;
+        self
+    }
+
+    pub fn with_story_points(mut self, story_points: u8) -> Self {
+        self.story_points = story_points;
+        self
+    }
+
+    fn ability_hooks(&self) -> impl Iterator<Item = AbilityHooks> + '_ {
+        self.abilities.iter().map(abilities::hooks)
+    }
+
+    /// Extra damage the combatant does with bare hands.
+    pub fn unarmed_damage(&self) -> u8 {
+        self.ability_hooks().map(|hooks| hooks.unarmed_damage).sum()
+    }
+
+    /// Whether checks of the skill are skipped when moving between zones.
+    pub fn bypasses_check(&self, skill: SkillKey) -> bool {
+        self.ability_hooks()
+            .any(|hooks| hooks.bypass_check == Some(skill))
+    }
+
+    pub fn acts_when_surprised(&self) -> bool {
+        self.ability_hooks().any(|hooks| hooks.acts_when_surprised)
+    }
+
+    pub fn skill(&self, key: SkillKey) -> u8 {
+        self.skills.get(key)
+    }
+
+    pub fn weapon(&self) -> Option<&Attack> {
+        self.attacks.get(self.equipped)
+    }
+
+    pub fn ammo(&self, attack: usize) -> Option<u8> {
+        self.ammo.get(attack).copied().flatten()
+    }
+
+    /// Whether the attack can be made, not being out of shots.
+    pub fn is_loaded(&self, attack: usize) -> bool {
+        self.ammo(attack) != Some(0)
+    }
+
+    /// Whether the attack is down on shots and can be reloaded.
+    pub fn can_reload(&self, attack: usize) -> bool {
+        match (self.ammo(attack), self.attacks.get(attack)) {
+            (Some(ammo), Some(attack)) => attack.capacity().is_some_and(|shots| ammo < shots),
+            _ => false,
+        }
+    }
+
+    /// How many attacks the combatant gets in a turn.
+    pub fn attacks_per_turn(&self) -> u8 {
+        self.tags
+            .iter()
+            .find_map(|tag| match tag {
+                DCTag::MultipleAttacks(attacks) => Some(*attacks),
+                _ => None,
+            })
+            .unwrap_or(1)
+    }
+
+    pub fn has_status(&self, kind: StatusKind) -> bool {
+        self.statuses.iter().any(|status| status.kind == kind)
+    }
+
+    pub fn is_incapacitated(&self) -> bool {
+        self.vitality == 0
+    }
+
+    pub fn can_act(&self) -> bool {
+        !self.is_incapacitated()
+    }
+}
+
+/// The order in which combatants act, highest initiative first.
+///
+/// Ties are broken in favour of player characters, then by the order the
+/// combatants were added, so the same initiatives always give the same order.
+#[derive(Clone, Debug, Default)]
+pub struct TurnOrder {
+    order: Vec<CombatantId>,
+    current: usize,
+    round: u32,
+}
+
+impl TurnOrder {
+    pub fn new(combatants: &[Combatant]) -> Self {
+        let mut sorted: Vec<&Combatant> = combatants.iter().collect();
+        sorted.sort_by(|a, b| {
+            b.initiative
+                .cmp(&a.initiative)
+                .then((b.side == Side::Players).cmp(&(a.side == Side::Players)))
+        });
+        Self {
+            order: sorted.iter().map(|combatant| combatant.id).collect(),
+            current: 0,
+            round: 0,
+        }
+    }
+
+    /// Start the first round, returning the first combatant able to act.
+    pub fn start(&mut self, can_act: impl Fn(CombatantId) -> bool) -> Option<CombatantId> {
+        self.round = 1;
+        self.current = 0;
+        match self.order.first() {
+            Some(&id) if can_act(id) => Some(id),
+            Some(_) => self.advance(can_act),
+            None => None,
+        }
+    }
+
+    /// Move on to the next combatant able to act, starting a new round when
+    /// the end of the order is passed.
+    pub fn advance(&mut self, can_act: impl Fn(CombatantId) -> bool) -> Option<CombatantId> {
+        for _ in 0..self.order.len() {
+            self.current += 1;
+            if self.current >= self.order.len() {
+                self.current = 0;
+                self.round += 1;
+            }
+            let id = self.order[self.current];
+            if can_act(id) {
+                return Some(id);
+            }
+        }
+        None
+    }
+
+    pub fn current(&self) -> Option<CombatantId> {
+        if self.round == 0 {
+            return None;
+        }
+        self.order.get(self.current).copied()
+    }
+
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    pub fn order(&self) -> &[CombatantId] {
+        &self.order
+    }
+}
+
+/// Where an [`Objective`] stands. Protecting someone is never met, only
+/// failed once they go down.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectiveStatus {
+    Pending,
+    Met,
+    Failed,
+}
+
+/// How a combat ended for the players.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Victory,
+    Defeat,
+    /// The player characters got away.
+    Fled,
+}
+
+/// What is left once a combat is over.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CombatResult {
+    pub outcome: Outcome,
+    pub rounds: u32,
+    pub down: Vec<CombatantId>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    EndTurn,
+    Move { to: String },
+    Attack { target: CombatantId, attack: usize },
+    GetUp,
+    SwitchWeapon,
+    Reload { attack: usize },
+}
+
+/// What the acting combatant has done so far this turn.
+///
+/// A turn has one move and one action. The action goes to attacking, getting
+/// up, reloading or an extra move, while switching weapons once is free.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TurnSoFar {
+    pub moved: bool,
+    pub extra_moved: bool,
+    pub attacks: u8,
+    pub got_up: bool,
+    pub reloaded: bool,
+    pub switched: bool,
+}
+
+impl TurnSoFar {
+    /// Note down an action taken.
+    pub fn record(&mut self, action: &Action) {
+        match action {
+            Action::Move { .. } if self.moved => self.extra_moved = true,
+            Action::Move { .. } => self.moved = true,
+            Action::Attack { .. } => self.attacks += 1,
+            Action::GetUp => self.got_up = true,
+            Action::SwitchWeapon => self.switched = true,
+            Action::Reload { .. } => self.reloaded = true,
+            Action::EndTurn => {}
+        }
+    }
+
+    /// Whether the action of the turn is spent.
+    pub fn has_acted(&self) -> bool {
+        self.got_up || self.reloaded || self.extra_moved || self.attacks > 0
+    }
+
+    pub fn can_move(&self) -> bool {
+        !self.moved
+    }
+
+    /// Whether the action can still go to a second move.
+    pub fn can_extra_move(&self) -> bool {
+        self.moved && !self.has_acted()
+    }
+
+    /// Whether another attack fits in the turn, given how many attacks the
+    /// combatant gets.
+    pub fn can_attack(&self, per_turn: u8) -> bool {
+        !self.got_up && !self.reloaded && !self.extra_moved && self.attacks < per_turn
+    }
+
+    pub fn can_get_up(&self) -> bool {
+        !self.has_acted()
+    }
+
+    pub fn can_reload(&self) -> bool {
+        !self.has_acted()
+    }
+
+    pub fn can_switch(&self) -> bool {
+        !self.switched
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CombatEvent {
+    RoundStarted {
+        round: u32,
+    },
+    TurnStarted {
+        combatant: CombatantId,
+    },
+    TurnEnded {
+        combatant: CombatantId,
+    },
+    /// A combatant caught by surprise tried to react in the first round.
+    SurpriseChecked {
+        combatant: CombatantId,
+        check: CheckResult,
+    },
+    /// A complication of the combatant got in the way.
+    ComplicationTriggered {
+        combatant: CombatantId,
+        complication: ComplicationType,
+    },
+    /// The turn started and was lost straight away, to surprise or to a
+    /// status effect.
+    TurnSkipped {
+        combatant: CombatantId,
+    },
+    Moved {
+        combatant: CombatantId,
+        from: String,
+        to: String,
+    },
+    MoveChecked {
+        combatant: CombatantId,
+        to: String,
+        skill: SkillKey,
+        check: CheckResult,
+    },
+    Attacked {
+        attacker: CombatantId,
+        target: CombatantId,
+        attack: String,
+        check: CheckResult,
+    },
+    Damaged {
+        combatant: CombatantId,
+        damage: u8,
+        vitality: u8,
+    },
+    Incapacitated {
+        combatant: CombatantId,
+    },
+    /// A hit took down one member of a squad.
+    SquadMemberDown {
+        combatant: CombatantId,
+        left: u8,
+    },
+    FellProne {
+        combatant: CombatantId,
+    },
+    GotUp {
+        combatant: CombatantId,
+    },
+    WeaponSwitched {
+        combatant: CombatantId,
+        equipped: usize,
+        weapon: String,
+    },
+    AmmoSpent {
+        combatant: CombatantId,
+        attack: usize,
+        ammo: u8,
+    },
+    Reloaded {
+        combatant: CombatantId,
+        attack: usize,
+        ammo: u8,
+    },
+    StatusApplied {
+        combatant: CombatantId,
+        status: StatusKind,
+        rounds: u32,
+    },
+    StatusChecked {
+        combatant: CombatantId,
+        status: StatusKind,
+        skill: SkillKey,
+        check: CheckResult,
+    },
+    StatusExpired {
+        combatant: CombatantId,
+        status: StatusKind,
+    },
+    /// A check was flipped, the roll read with its dice swapped.
+    Flipped {
+        combatant: CombatantId,
+        from: D100,
+        to: D100,
+        flips_left: u8,
+    },
+    /// A story point was spent on a check.
+    StoryPointSpent {
+        combatant: CombatantId,
+        spend: StorySpend,
+        from: CheckResult,
+        to: CheckResult,
+        story_points_left: u8,
+    },
+}
+
+#[non_exhaustive]
+#[derive(Debug, Error, PartialEq)]
+pub enum RulesError {
+    #[error("Nobody is acting")]
+    NoCurrentCombatant,
+    #[error("Unknown combatant: {0:?}")]
+    UnknownCombatant(CombatantId),
+    #[error("Unknown zone: {0}")]
+    UnknownZone(String),
+    #[error("Zone {to} can not be reached from {from}")]
+    NotAdjacent { from: String, to: String },
+    #[error("No attack number {0}")]
+    UnknownAttack(usize),
+    #[error("{0:?} is out of reach")]
+    OutOfReach(CombatantId),
+    #[error("{0:?} is already down")]
+    AlreadyDown(CombatantId),
+    #[error("{0:?} is on the same side")]
+    SameSide(CombatantId),
+    #[error("Invalid check {0:?}")]
+    InvalidCheck(String),
+    #[error("{0:?} is not prone")]
+    NotProne(CombatantId),
+    #[error("{0:?} has already used the action of the turn")]
+    AlreadyActed(CombatantId),
+    #[error("{0:?} has no move left this turn")]
+    NoMoveLeft(CombatantId),
+    #[error("{0:?} has already switched weapons this turn")]
+    AlreadySwitched(CombatantId),
+    #[error("{0:?} has nothing to switch to")]
+    NothingToSwitch(CombatantId),
+    #[error("Attack number {0} is out of ammo")]
+    OutOfAmmo(usize),
+    #[error("Attack number {0} has nothing to reload")]
+    NothingToReload(usize),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CombatState {
+    pub combatants: Vec<Combatant>,
+    pub zones: HashMap<String, Vec<ZoneMove>>,
+    pub objectives: Vec<Objective>,
+    pub turn_order: TurnOrder,
+    pub this_turn: TurnSoFar,
+    /// Flips the player characters share.
+    pub player_flips: u8,
+    /// The side caught by surprise, who only act in the first round when
+    /// they notice what is going on.
+    pub surprised: Option<Side>,
+}
+
+impl CombatState {
+    pub fn new(combat_map: &CombatMap, combatants: Vec<Combatant>) -> Self {
+        let zones = combat_map
+            .zones
+            .iter()
+            .map(|zone| (zone.tag.clone(), zone.adjacent.clone()))
+            .collect();
+        let turn_order = TurnOrder::new(&combatants);
+        Self {
+            combatants,
+            zones,
+            objectives: combat_map.objectives.clone(),
+            turn_order,
+            this_turn: TurnSoFar::default(),
+            player_flips: PLAYER_FLIPS,
+            surprised: combat_map.surprised,
+        }
+    }
+
+    pub fn combatant(&self, id: CombatantId) -> Option<&Combatant> {
+        self.combatants.iter().find(|combatant| combatant.id == id)
+    }
+
+    fn combatant_mut(&mut self, id: CombatantId) -> Result<&mut Combatant, RulesError> {
+        self.combatants
+            .iter_mut()
+            .find(|combatant| combatant.id == id)
+            .ok_or(RulesError::UnknownCombatant(id))
+    }
+
+    pub fn current(&self) -> Option<CombatantId> {
+        self.turn_order.current()
+    }
+
+    pub fn round(&self) -> u32 {
+        self.turn_order.round()
+    }
+
+    pub fn can_act(&self, id: CombatantId) -> bool {
+        self.combatant(id)
+            .map(|combatant| combatant.can_act())
+            .unwrap_or(false)
+    }
+
+    pub fn adjacent_zones(&self, zone: &str) -> Vec<&ZoneMove> {
+        self.zones
+            .get(zone)
+            .map(|adjacent| adjacent.iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// How far zone `to` is from zone `from`, `None` if there is no way
+    /// there.
+    pub fn range(&self, from: &str, to: &str) -> Option<RangeBand> {
+        self.path(from, to)
+            .map(|path| RangeBand::from_moves(path.len()))
+    }
+
+    /// The opponents still standing that the attacker can reach with the
+    /// given attack.
+    pub fn targets(&self, attacker: CombatantId, attack: usize) -> Vec<CombatantId> {
+        let Some(attacker) = self.combatant(attacker) else {
+            return Vec::new();
+        };
+        let Some(attack) = attacker.attacks.get(attack) else {
+            return Vec::new();
+        };
+        self.combatants
+            .iter()
+            .filter(|target| {
+                target.side != attacker.side
+                    && !target.is_incapacitated()
+                    && self
+                        .range(&attacker.zone, &target.zone)
+                        .and_then(|band| range_modifier(attack, band))
+                        .is_some()
+            })
+            .map(|target| target.id)
+            .collect()
+    }
+
+    /// The zones to walk through to get from one zone to another, not
+    /// counting the zone walked from.
+    pub fn path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let mut came_from: HashMap<&str, &str> = HashMap::from([(from, from)]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(zone) = queue.pop_front() {
+            if zone == to {
+                let mut path = Vec::new();
+                let mut step = to;
+                while step != from {
+                    path.push(step.to_string());
+                    step = came_from[step];
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for next in self.adjacent_zones(zone) {
+                if !came_from.contains_key(next.tag.as_str()) {
+                    came_from.insert(&next.tag, zone);
+                    queue.push_back(&next.tag);
+                }
+            }
+        }
+        None
+    }
+
+    /// How far along an objective is, as what is done out of what is needed.
+    pub fn objective_progress(&self, objective: &Objective) -> (u32, u32) {
+        let standing = || {
+            self.combatants
+                .iter()
+                .filter(|combatant| combatant.side == Side::Players)
+                .filter(|combatant| !combatant.is_incapacitated())
+        };
+        // How many with the tag are down, out of how many there are.
+        let tagged = |tag: &str| {
+            let tagged = self
+                .combatants
+                .iter()
+                .filter(|combatant| combatant.slug == tag);
+            let (down, up): (Vec<&Combatant>, Vec<&Combatant>) =
+                tagged.partition(|combatant| combatant.is_incapacitated());
+            (down.len() as u32, (down.len() + up.len()) as u32)
+        };
+        match objective {
+            Objective::Reach { zone, count } => {
+                let needed = count.unwrap_or_else(|| standing().count());
+                let done = standing()
+                    .filter(|combatant| &combatant.zone == zone)
+                    .count();
+                (done as u32, needed as u32)
+            }
+            Objective::Escape { zone } => {
+                let needed = standing().count();
+                let done = standing()
+                    .filter(|combatant| &combatant.zone == zone)
+                    .count();
+                (done as u32, needed as u32)
+            }
+            Objective::Survive { rounds } => (self.round().saturating_sub(1), *rounds),
+            Objective::Defeat { tag } => tagged(tag),
+            Objective::Protect { tag } => {
+                let (down, total) = tagged(tag);
+                (total - down, total)
+            }
+        }
+    }
+
+    pub fn objective_status(&self, objective: &Objective) -> ObjectiveStatus {
+        let (done, needed) = self.objective_progress(objective);
+        match objective {
+            Objective::Protect { .. } if done < needed => ObjectiveStatus::Failed,
+            Objective::Protect { .. } => ObjectiveStatus::Pending,
+            _ if needed > 0 && done >= needed => ObjectiveStatus::Met,
+            _ => ObjectiveStatus::Pending,
+        }
+    }
+
+    /// Whether the combat is over: lost when every player character is down
+    /// or an objective of the map failed, won when every director character
+    /// is down or any objective of the map is met, and fled when the player
+    /// characters made their escape.
+    pub fn outcome(&self) -> Option<Outcome> {
+        let all_down = |side: Side| {
+            let mut on_side = self
+                .combatants
+                .iter()
+                .filter(|combatant| combatant.side == side)
+                .peekable();
+            on_side.peek().is_some() && on_side.all(|combatant| combatant.is_incapacitated())
+        };
+        let any_objective = |escape: bool, status: ObjectiveStatus| {
+            self.objectives
+                .iter()
+                .filter(|objective| matches!(objective, Objective::Escape { .. }) == escape)
+                .any(|objective| self.objective_status(objective) == status)
+        };
+        if all_down(Side::Players) || any_objective(false, ObjectiveStatus::Failed) {
+            Some(Outcome::Defeat)
+        } else if all_down(Side::Director) || any_objective(false, ObjectiveStatus::Met) {
+            Some(Outcome::Victory)
+        } else if any_objective(true, ObjectiveStatus::Met) {
+            Some(Outcome::Fled)
+        } else {
+            None
+        }
+    }
+
+    pub fn result(&self) -> Option<CombatResult> {
+        let outcome = self.outcome()?;
+        Some(CombatResult {
+            outcome,
+            rounds: self.round(),
+            down: self
+                .combatants
+                .iter()
+                .filter(|combatant| combatant.is_incapacitated())
+                .map(|combatant| combatant.id)
+                .collect(),
+        })
+    }
+
+    pub fn is_surprise_round(&self) -> bool {
+        self.surprised.is_some() && self.round() == 1
+    }
+
+    /// Start the first round of combat.
+    pub fn begin(
+        &self,
+        dice: &mut impl Dice,
+    ) -> Result<(CombatState, Vec<CombatEvent>), RulesError> {
+        self.begin_with_flips(dice, &mut director::DirectorFlips)
+    }
+
+    /// Start the first round, asking the flipper about every check of the
+    /// opening turn that could be flipped.
+    pub fn begin_with_flips(
+        &self,
+        dice: &mut impl Dice,
+        flipper: &mut impl Flipper,
+    ) -> Result<(CombatState, Vec<CombatEvent>), RulesError> {
+        let mut state = self.clone();
+        let mut events = Vec::new();
+        let first = state.turn_order.start(|id| self.can_act(id));
+        events.push(CombatEvent::RoundStarted {
+            round: state.round(),
+        });
+        if let Some(combatant) = first {
+            events.push(CombatEvent::TurnStarted { combatant });
+            if state.start_turn(combatant, dice, flipper, &mut events)? {
+                events.push(CombatEvent::TurnSkipped { combatant });
+                state.end_turn(combatant, dice, flipper, &mut events)?;
+            }
+        }
+        Ok((state, events))
+    }
+
+    /// Resolve an action taken by the combatant whose turn it is, with the
+    /// director characters spending flips as they see fit.
+    pub fn apply(
+        &self,
+        action: &Action,
+        dice: &mut impl Dice,
+    ) -> Result<(CombatState, Vec<CombatEvent>), RulesError> {
+        self.apply_with_flips(action, dice, &mut director::DirectorFlips)
+    }
+
+    /// Resolve an action, asking the flipper about every check that could be
+    /// flipped.
+    pub fn apply_with_flips(
+        &self,
+        action: &Action,
+        dice: &mut impl Dice,
+        flipper: &mut impl Flipper,
+    ) -> Result<(CombatState, Vec<CombatEvent>), RulesError> {
+        let actor = self.current().ok_or(RulesError::NoCurrentCombatant)?;
+        let mut state = self.clone();
+        let mut events = Vec::new();
+        match action {
+            Action::EndTurn => state.end_turn(actor, dice, flipper, &mut events)?,
+            Action::Move { to } => state.move_to(actor, to, dice, flipper, &mut events)?,
+            Action::Attack { target, attack } => {
+                state.attack(actor, *target, *attack, dice, flipper, &mut events)?
+            }
+            Action::GetUp => state.get_up(actor, &mut events)?,
+            Action::SwitchWeapon => state.switch_weapon(actor, &mut events)?,
+            Action::Reload { attack } => state.reload(actor, *attack, &mut events)?,
+        }
+        state.this_turn.record(action);
+        if *action == Action::EndTurn {
+            state.this_turn = TurnSoFar::default();
+        }
+        Ok((state, events))
+    }
+
+    /// Pass the turn on, past anyone whose status effects cost them theirs.
+    fn end_turn(
+        &mut self,
+        actor: CombatantId,
+        dice: &mut impl Dice,
+        flipper: &mut impl Flipper,
+        events: &mut Vec<CombatEvent>,
+    ) -> Result<(), RulesError> {
+        let mut actor = actor;
+        loop {
+            events.push(CombatEvent::TurnEnded { combatant: actor });
+            let round = self.round();
+            let combatants = &self.combatants;
+            let can_act = |id| {
+                combatants
+                    .iter()
+                    .any(|combatant| combatant.id == id && combatant.can_act())
+            };
+            let Some(combatant) = self.turn_order.advance(can_act) else {
+                return Ok(());
+            };
+            if self.round() != round {
+                events.push(CombatEvent::RoundStarted {
+                    round: self.round(),
+                });
+            }
+            events.push(CombatEvent::TurnStarted { combatant });
+            if !self.start_turn(combatant, dice, flipper, events)? {
+                return Ok(());
+            }
+            events.push(CombatEvent::TurnSkipped { combatant });
+            actor = combatant;
+        }
+    }
+
+    /// Whatever comes at the start of a turn, giving back whether the turn is
+    /// lost: reacting to a surprise and then the status effects.
+    fn start_turn(
+        &mut self,
+        combatant: CombatantId,
+        dice: &mut impl Dice,
+        flipper: &mut impl Flipper,
+        events: &mut Vec<CombatEvent>,
+    ) -> Result<bool, RulesError> {
+        let actor = self
+            .combatant(combatant)
+            .ok_or(RulesError::UnknownCombatant(combatant))?;
+        let caught = self.is_surprise_round()
+            && self.surprised == Some(actor.side)
+            && !actor.acts_when_surprised();
+        let alertness = actor.skill(SkillKey::Alertness);
+        let hindrances: Vec<(ComplicationType, i16)> = actor
+            .complications
+            .iter()
+            .map(|complication| {
+                let hooks = complications::hooks(complication);
+                (*complication, hooks.surprise_alertness)
+            })
+            .filter(|(_complication, modifier)| *modifier != 0)
+            .collect();
+        let mut skip_turn = false;
+        if caught {
+            let mut skill_check = SkillCheck::new(alertness);
+            for (complication, modifier) in hindrances {
+                skill_check = skill_check.with_modifier(modifier);
+                events.push(CombatEvent::ComplicationTriggered {
+                    combatant,
+                    complication,
+                });
+                // Earned right away, so it can go into this very check.
+                let actor = self.combatant_mut(combatant)?;
+                actor.story_points = actor.story_points.saturating_add(COMPLICATION_AWARD);
+            }
+            let check = self.roll_check(combatant, skill_check, dice, flipper, events)?;
+            events.push(CombatEvent::SurpriseChecked { combatant, check });
+            skip_turn = !check.is_success();
+        }
+        let lost_to_effects = self.start_turn_effects(combatant, dice, flipper, events)?;
+        Ok(skip_turn || lost_to_effects)
+    }
+
+    fn move_to(
+        &mut self,
+        actor: CombatantId,
+        to: &str,
+        dice: &mut impl Dice,
+        flipper: &mut impl Flipper,
+        events: &mut Vec<CombatEvent>,
+    ) -> Result<(), RulesError> {
+        if !self.zones.contains_key(to) {
+            return Err(RulesError::UnknownZone(to.to_string()));
+        }
+        if !self.this_turn.can_move() && !self.this_turn.can_extra_move() {
+            return Err(RulesError::NoMoveLeft(actor));
+        }
+        let from = self.combatant_mut(actor)?.zone.clone();
+        let Some(zone_move) = self
+            .adjacent_zones(&from)
+            .into_iter()
+            .find(|zone| zone.tag == to)
+            .cloned()
+        else {
+            return Err(RulesError::NotAdjacent {
+                from,
+                to: to.to_string(),
+            });
+        };
+        let mover = self.combatant_mut(actor)?;
+        let prone = mover.prone;
+        let gate = match &zone_move.check {
+            Some(check) => Some(
+                check
+                    .parse::<CheckSpec>()
+                    .map_err(|_| RulesError::InvalidCheck(check.clone()))?,
+            ),
+            None => None,
+        };
+        // Abilities can get past some gates without a check, and crawling
+        // along needs an Agility check even where walking does not.
+        let spec = match gate {
+            Some(spec) if !mover.bypasses_check(spec.skill) => Some(spec),
+            _ if prone => Some(CheckSpec {
+                skill: SkillKey::Agility,
+                modifier: 0,
+            }),
+            _ => None,
+        };
+        if let Some(spec) = spec {
+            let skill = self.combatant_mut(actor)?.skill(spec.skill);
+            let mut skill_check = spec.against(skill);
+            if prone {
+                skill_check = skill_check.with_modifier(PRONE_MODIFIER);
+            }
+            let check = self.roll_check(actor, skill_check, dice, flipper, events)?;
+            events.push(CombatEvent::MoveChecked {
+                combatant: actor,
+                to: to.to_string(),
+                skill: spec.skill,
+                check,
+            });
+            if !check.is_success() {
+                self.knock_down(actor, events)?;
+                return Ok(());
+            }
+        }
+        self.combatant_mut(actor)?.zone = to.to_string();
+        events.push(CombatEvent::Moved {
+            combatant: actor,
+            from,
+            to: to.to_string(),
+        });
+        Ok(())
+    }
+
+    fn attack(
+        &mut self,
+        attacker: CombatantId,
+        target: CombatantId,
+        attack: usize,
+        dice: &mut impl Dice,
+        flipper: &mut impl Flipper,
+        events: &mut Vec<CombatEvent>,
+    ) -> Result<(), RulesError> {
+        let from = self
+            .combatant(attacker)
+            .ok_or(RulesError::UnknownCombatant(attacker))?;
+        let to = self
+            .combatant(target)
+            .ok_or(RulesError::UnknownCombatant(target))?;
+        let attack_index = attack;
+        let attack = from
+            .attacks
+            .get(attack)
+            .cloned()
+            .ok_or(RulesError::UnknownAttack(attack))?;
+        if !from.is_loaded(attack_index) {
+            return Err(RulesError::OutOfAmmo(attack_index));
+        }
+        if to.side == from.side {
+            return Err(RulesError::SameSide(target));
+        }
+        if to.is_incapacitated() {
+            return Err(RulesError::AlreadyDown(target));
+        }
+        let Some(range_modifier) = self
+            .range(&from.zone, &to.zone)
+            .and_then(|band| range_modifier(&attack, band))
+        else {
+            return Err(RulesError::OutOfReach(target));
+        };
+        if !self.this_turn.can_attack(from.attacks_per_turn()) {
+            return Err(RulesError::AlreadyActed(attacker));
+        }
+
+        let mut skill_check = SkillCheck::new(attack.skill).with_modifier(range_modifier);
+        if let Some(members) = from.squad {
+            let bonus = SQUAD_BONUS * members.saturating_sub(1) as i16;
+            skill_check = skill_check.with_modifier(bonus.min(SQUAD_MAX_BONUS));
+        }
+        if from.prone {
+            skill_check = skill_check.with_modifier(PRONE_MODIFIER);
+        }
+        let signature_gear = attack.tags.contains(&AttackTag::Signature);
+        let check =
+            self.roll_gear_check(attacker, skill_check, signature_gear, dice, flipper, events)?;
+        events.push(CombatEvent::Attacked {
+            attacker,
+            target,
+            attack: attack.name.clone(),
+            check,
+        });
+        let from = self.combatant_mut(attacker)?;
+        if let Some(Some(ammo)) = from.ammo.get_mut(attack_index) {
+            *ammo -= 1;
+            events.push(CombatEvent::AmmoSpent {
+                combatant: attacker,
+                attack: attack_index,
+                ammo: *ammo,
+            });
+        }
+        if check.is_success() {
+            let mut damage = attack.damage;
+            if attack.tags.contains(&AttackTag::Unarmed) {
+                damage += self.combatant_mut(attacker)?.unarmed_damage();
+            }
+            self.damage(target, damage, events)?;
+            if attack
+                .tags
+                .iter()
+                .any(|tag| matches!(tag, AttackTag::Knockback | AttackTag::Trip))
+            {
+                self.knock_down(target, events)?;
+            }
+            if attack.tags.contains(&AttackTag::Paralytic) {
+                self.add_status(
+                    target,
+                    StatusEffect::new(StatusKind::Paralysed, PARALYSED_ROUNDS),
+                    events,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn get_up(
+        &mut self,
+        actor: CombatantId,
+        events: &mut Vec<CombatEvent>,
+    ) -> Result<(), RulesError> {
+        if !self.this_turn.can_get_up() {
+            return Err(RulesError::AlreadyActed(actor));
+        }
+        let combatant = self.combatant_mut(actor)?;
+        if !combatant.prone {
+            return Err(RulesError::NotProne(actor));
+        }
+        combatant.prone = false;
+        events.push(CombatEvent::GotUp { combatant: actor });
+        Ok(())
+    }
+
+    fn reload(
+        &mut self,
+        actor: CombatantId,
+        attack: usize,
+        events: &mut Vec<CombatEvent>,
+    ) -> Result<(), RulesError> {
+        if !self.this_turn.can_reload() {
+            return Err(RulesError::AlreadyActed(actor));
+        }
+        let combatant = self.combatant_mut(actor)?;
+        if !combatant.can_reload(attack) {
+            return Err(RulesError::NothingToReload(attack));
+        }
+        let shots = combatant.attacks[attack].capacity().unwrap_or_default();
+        combatant.ammo[attack] = Some(shots);
+        events.push(CombatEvent::Reloaded {
+            combatant: actor,
+            attack,
+            ammo: shots,
+        });
+        Ok(())
+    }
+
+    /// Put the next attack in hand, going back to the first after the last.
+    fn switch_weapon(
+        &mut self,
+        actor: CombatantId,
+        events: &mut Vec<CombatEvent>,
+    ) -> Result<(), RulesError> {
+        if !self.this_turn.can_switch() {
+            return Err(RulesError::AlreadySwitched(actor));
+        }
+        let combatant = self.combatant_mut(actor)?;
+        if combatant.attacks.len() < 2 {
+            return Err(RulesError::NothingToSwitch(actor));
+        }
+        combatant.equipped = (combatant.equipped + 1) % combatant.attacks.len();
+        events.push(CombatEvent::WeaponSwitched {
+            combatant: actor,
+            equipped: combatant.equipped,
+            weapon: combatant.attacks[combatant.equipped].name.clone(),
+        });
+        Ok(())
+    }
+
+    fn knock_down(
+        &mut self,
+        combatant: CombatantId,
+        events: &mut Vec<CombatEvent>,
+    ) -> Result<(), RulesError> {
+        let target = self.combatant_mut(combatant)?;
+        if !target.prone && !target.is_incapacitated() {
+            target.prone = true;
+            events.push(CombatEvent::FellProne { combatant });
+        }
+        Ok(())
+    }
+
+    fn damage(
+        &mut self,
+        combatant: CombatantId,
+        damage: u8,
+        events: &mut Vec<CombatEvent>,
+    ) -> Result<(), RulesError> {
+        let target = self.combatant_mut(combatant)?;
+        if let Some(members) = target.squad.as_mut() {
+            *members = members.saturating_sub(1);
+            events.push(CombatEvent::SquadMemberDown {
+                combatant,
+                left: *members,
+            });
+            if *members == 0 {
+                target.vitality = 0;
+                events.push(CombatEvent::Incapacitated { combatant });
+            }
+            return Ok(());
+        }
+        target.vitality = target.vitality.saturating_sub(damage);
+        events.push(CombatEvent::Damaged {
+            combatant,
+            damage,
+            vitality: target.vitality,
+        });
+        if target.is_incapacitated() {
+            events.push(CombatEvent::Incapacitated { combatant });
+        }
+        Ok(())
+    }
+}
+
+/// Initiative from an Alertness check, given the d100 roll.
+pub fn initiative(alertness: u8, roll: D100) -> u8 {
+    let check = SkillCheck::new(alertness).resolve(roll);
+    match check.outcome {
+        CheckOutcome::CriticalSuccess => 0,
+        CheckOutcome::Success => check.degree(),
+        CheckOutcome::Failure => roll.tens() + roll.ones(),
+        CheckOutcome::CriticalFailure => roll.tens() + roll.ones() + 10,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::ron;
+
+    use super::*;
+    use crate::characters::SkillType;
+    use crate::dice::LoadedDice;
+
+    const PC: CombatantId = CombatantId(1);
+    const DC: CombatantId = CombatantId(2);
+    const OTHER_DC: CombatantId = CombatantId(3);
+    const OTHER_PC: CombatantId = CombatantId(4);
+
+    /// Three zones in a row, `a` next to `b` next to `c`.
+    pub(super) fn corridor() -> CombatMap {
+        ron::from_str(
+            r#"(
+                bitmap: "corridor",
+                zones: [
+                    (position: (x_pos: 0.0, y_pos: 0.0, height: 10.0, width: 10.0),
+                     name: "A", tag: "a", adjacent: [(check: None, tag: "b")]),
+                    (position: (x_pos: 10.0, y_pos: 0.0, height: 10.0, width: 10.0),
+                     name: "B", tag: "b", adjacent: [(check: None, tag: "a"), (check: None, tag: "c")]),
+                    (position: (x_pos: 20.0, y_pos: 0.0, height: 10.0, width: 10.0),
+                     name: "C", tag: "c", adjacent: [(check: None, tag: "b")]),
+                ],
+                start_positions: [],
+            )"#,
+        )
+        .unwrap()
+    }
+
+    pub(super) fn fighter(id: CombatantId, side: Side, initiative: u8) -> Combatant {
+        Combatant::new(id, side, initiative, 10, "a").with_attacks(vec![Attack::unarmed(50)])
+    }
+
+    pub(super) fn begin(combatants: Vec<Combatant>, dice: &mut LoadedDice) -> CombatState {
+        let (state, _events) = CombatState::new(&corridor(), combatants)
+            .begin(dice)
+            .unwrap();
+        state
+    }
+
+    #[test]
+    fn turn_order_goes_by_initiative_and_wraps_into_the_next_round() {
+        let mut dice = LoadedDice::new(&[50]);
+        let state = begin(
+            vec![
+                fighter(PC, Side::Players, 5),
+                fighter(DC, Side::Director, 9),
+                fighter(OTHER_DC, Side::Director, 7),
+            ],
+            &mut dice,
+        );
+        assert_eq!(state.turn_order.order(), &[DC, OTHER_DC, PC]);
+        assert_eq!((state.current(), state.round()), (Some(DC), 1));
+
+        let (state, _events) = state.apply(&Action::EndTurn, &mut dice).unwrap();
+        assert_eq!(state.current(), Some(OTHER_DC));
+        let (state, _events) = state.apply(&Action::EndTurn, &mut dice).unwrap();
+        assert_eq!(state.current(), Some(PC));
+        let (state, events) = state.apply(&Action::EndTurn, &mut dice).unwrap();
+        assert_eq!((state.current(), state.round()), (Some(DC), 2));
+        assert!(events.contains(&CombatEvent::RoundStarted { round: 2 }));
+    }
+
+    #[test]
+    fn ties_in_initiative_go_to_the_player_characters() {
+        let order = TurnOrder::new(&[
+            fighter(DC, Side::Director, 6),
+            fighter(PC, Side::Players, 6),
+            fighter(OTHER_DC, Side::Director, 6),
+        ]);
+        assert_eq!(order.order(), &[PC, DC, OTHER_DC]);
+    }
+
+    #[test]
+    fn a_second_attack_in_a_turn_is_rejected() {
+        let mut dice = LoadedDice::new(&[90]);
+        let state = begin(
+            vec![
+                fighter(PC, Side::Players, 9),
+                fighter(DC, Side::Director, 1),
+            ],
+            &mut dice,
+        );
+        let attack = Action::Attack {
+            target: DC,
+            attack: 0,
+        };
+        let (state, _events) = state.apply(&attack, &mut dice).unwrap();
+        assert_eq!(
+            state.apply(&attack, &mut dice).unwrap_err(),
+            RulesError::AlreadyActed(PC)
+        );
+    }
+
+    #[test]
+    fn attacking_someone_on_the_same_side_is_rejected() {
+        let mut dice = LoadedDice::new(&[21]);
+        let state = begin(
+            vec![
+                fighter(DC, Side::Director, 9),
+                fighter(OTHER_DC, Side::Director, 5),
+                fighter(PC, Side::Players, 1),
+            ],
+            &mut dice,
+        );
+        assert_eq!(
+            state
+                .apply(
+                    &Action::Attack {
+                        target: OTHER_DC,
+                        attack: 0,
+                    },
+                    &mut dice,
+                )
+                .unwrap_err(),
+            RulesError::SameSide(OTHER_DC)
+        );
+    }
+
+    #[test]
+    fn moving_to_a_zone_that_is_not_adjacent_is_rejected() {
+        let mut dice = LoadedDice::new(&[50]);
+        let state = begin(vec![fighter(PC, Side::Players, 9)], &mut dice);
+        let to_c = Action::Move {
+            to: "c".to_string(),
+        };
+        assert_eq!(
+            state.apply(&to_c, &mut dice).unwrap_err(),
+            RulesError::NotAdjacent {
+                from: "a".to_string(),
+                to: "c".to_string(),
+            }
+        );
+        let to_b = Action::Move {
+            to: "b".to_string(),
+        };
+        let (state, _events) = state.apply(&to_b, &mut dice).unwrap();
+        let (state, _events) = state.apply(&to_c, &mut dice).unwrap();
+        assert_eq!(state.combatant(PC).unwrap().zone, "c");
+        // Both the move and the action went to walking.
+        assert_eq!(
+            state.apply(&to_b, &mut dice).unwrap_err(),
+            RulesError::NoMoveLeft(PC)
+        );
+    }
+
+    #[test]
+    fn end_turn_skips_combatants_who_are_down() {
+        let mut dice = LoadedDice::new(&[50]);
+        let mut state = CombatState::new(
+            &corridor(),
+            vec![
+                fighter(PC, Side::Players, 9),
+                fighter(DC, Side::Director, 7),
+                fighter(OTHER_DC, Side::Director, 5),
+            ],
+        );
+        state.combatant_mut(DC).unwrap().vitality = 0;
+        let (state, _events) = state.begin(&mut dice).unwrap();
+        let (state, events) = state.apply(&Action::EndTurn, &mut dice).unwrap();
+        assert_eq!(state.current(), Some(OTHER_DC));
+        assert!(!events.contains(&CombatEvent::TurnStarted { combatant: DC }));
+    }
+
+    #[test]
+    fn a_squad_attacks_with_a_capped_bonus_and_loses_a_member_to_each_hit() {
+        let mut dice = LoadedDice::new(&[21]);
+        let state = begin(
+            vec![
+                fighter(DC, Side::Director, 9).with_squad(5),
+                fighter(PC, Side::Players, 5),
+            ],
+            &mut dice,
+        );
+        let (state, events) = state
+            .apply(
+                &Action::Attack {
+                    target: PC,
+                    attack: 0,
+                },
+                &mut dice,
+            )
+            .unwrap();
+        let Some(CombatEvent::Attacked { check, .. }) = events
+            .iter()
+            .find(|event| matches!(event, CombatEvent::Attacked { .. }))
+        else {
+            panic!("no attack in {:?}", events);
+        };
+        assert_eq!(check.target, 50 + SQUAD_MAX_BONUS as u8);
+
+        let (state, _events) = state.apply(&Action::EndTurn, &mut dice).unwrap();
+        let (state, events) = state
+            .apply(
+                &Action::Attack {
+                    target: DC,
+                    attack: 0,
+                },
+                &mut dice,
+            )
+            .unwrap();
+        assert!(events.contains(&CombatEvent::SquadMemberDown {
+            combatant: DC,
+            left: 4
+        }));
+        assert_eq!(state.combatant(DC).unwrap().squad, Some(4));
+        assert!(!state.combatant(DC).unwrap().is_incapacitated());
+    }
+
+    #[test]
+    fn the_fight_ends_as_fled_once_everyone_standing_is_at_the_exit() {
+        let mut dice = LoadedDice::new(&[50]);
+        let mut state = CombatState::new(
+            &corridor(),
+            vec![
+                fighter(PC, Side::Players, 9),
+                fighter(OTHER_PC, Side::Players, 8),
+                Combatant::new(DC, Side::Director, 1, 10, "c"),
+            ],
+        );
+        state.objectives = vec![Objective::Escape {
+            zone: "b".to_string(),
+        }];
+        let (state, _events) = state.begin(&mut dice).unwrap();
+        let to_b = Action::Move {
+            to: "b".to_string(),
+        };
+        let (state, _events) = state.apply(&to_b, &mut dice).unwrap();
+        assert_eq!(state.outcome(), None);
+
+        let (state, _events) = state.apply(&Action::EndTurn, &mut dice).unwrap();
+        let (state, _events) = state.apply(&to_b, &mut dice).unwrap();
+        assert_eq!(state.outcome(), Some(Outcome::Fled));
+    }
+
+    #[test]
+    fn initiative_follows_the_alertness_check() {
+        for (roll, expected) in [
+            // A critical success, then the ones die of a success.
+            (33, 0),
+            (42, 2),
+            // The two dice added up on a failure, ten more on a fumble.
+            (50, 5),
+            (67, 13),
+            (77, 24),
+        ] {
+            assert_eq!(initiative(50, D100(roll)), expected, "roll {}", roll);
+        }
+    }
+
+    fn weapon(damage: u8, tags: Vec<AttackTag>) -> Attack {
+        Attack {
+            name: format!("{:?}", tags),
+            skill: 50,
+            damage,
+            tags,
+        }
+    }
+
+    #[test]
+    fn reach_and_range_modifiers_depend_on_the_weapon() {
+        let melee = weapon(2, vec![AttackTag::Melee]);
+        let short_range = weapon(3, vec![AttackTag::ShortRange]);
+        let rifle = weapon(4, vec![]);
+        for (attack, same, adjacent, far) in [
+            (&melee, Some(0), None, None),
+            (&short_range, Some(0), Some(-10), None),
+            (&rifle, Some(0), Some(0), Some(-10)),
+        ] {
+            assert_eq!(
+                [RangeBand::Same, RangeBand::Adjacent, RangeBand::Far]
+                    .map(|band| range_modifier(attack, band)),
+                [same, adjacent, far],
+                "{}",
+                attack.name
+            );
+        }
+    }
+
+    #[test]
+    fn targets_are_the_opponents_the_attack_reaches() {
+        let mut dice = LoadedDice::new(&[50]);
+        let at = |id, zone: &str| {
+            let mut dc = fighter(id, Side::Director, 1);
+            dc.zone = zone.to_string();
+            dc
+        };
+        let state = begin(
+            vec![
+                fighter(PC, Side::Players, 9).with_attacks(vec![
+                    weapon(2, vec![AttackTag::Melee]),
+                    weapon(3, vec![AttackTag::ShortRange]),
+                    weapon(4, vec![]),
+                ]),
+                at(DC, "a"),
+                at(OTHER_DC, "b"),
+                at(CombatantId(5), "c"),
+            ],
+            &mut dice,
+        );
+        assert_eq!(state.targets(PC, 0), vec![DC]);
+        assert_eq!(state.targets(PC, 1), vec![DC, OTHER_DC]);
+        assert_eq!(state.targets(PC, 2), vec![DC, OTHER_DC, CombatantId(5)]);
+
+        // The range penalty goes into the check.
+        let (_state, events) = state
+            .apply(
+                &Action::Attack {
+                    target: CombatantId(5),
+                    attack: 2,
+                },
+                &mut dice,
+            )
+            .unwrap();
+        assert!(events.iter().any(|event| matches!(
+            event,
+            CombatEvent::Attacked { check, .. } if check.target == 40
+        )));
+    }
+
+    /// Two zones with a locked door between them.
+    fn locked_door() -> CombatMap {
+        ron::from_str(
+            r#"(
+                bitmap: "door",
+                zones: [
+                    (position: (x_pos: 0.0, y_pos: 0.0, height: 10.0, width: 10.0),
+                     name: "A", tag: "a", adjacent: [(check: Some("Security"), tag: "b")]),
+                    (position: (x_pos: 10.0, y_pos: 0.0, height: 10.0, width: 10.0),
+                     name: "B", tag: "b", adjacent: [(check: Some("Security"), tag: "a")]),
+                ],
+                start_positions: [],
+            )"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn failing_the_check_of_a_gated_move_knocks_the_mover_down() {
+        // Over the default Security.
+        let mut dice = LoadedDice::new(&[72]);
+        let (state, _events) =
+            CombatState::new(&locked_door(), vec![fighter(PC, Side::Players, 9)])
+                .begin(&mut dice)
+                .unwrap();
+        let (state, events) = state
+            .apply(
+                &Action::Move {
+                    to: "b".to_string(),
+                },
+                &mut dice,
+            )
+            .unwrap();
+        assert!(events.iter().any(|event| matches!(
+            event,
+            CombatEvent::MoveChecked { skill: SkillKey::Security, check, .. } if !check.is_success()
+        )));
+        assert!(events.contains(&CombatEvent::FellProne { combatant: PC }));
+        let pc = state.combatant(PC).unwrap();
+        assert_eq!((pc.zone.as_str(), pc.prone), ("a", true));
+    }
+
+    #[test]
+    fn a_lock_picker_gets_through_a_locked_door_without_a_check() {
+        let mut dice = LoadedDice::new(&[72]);
+        let lock_picker =
+            fighter(PC, Side::Players, 9).with_abilities(vec![AbilityType::LockPicker]);
+        let (state, _events) = CombatState::new(&locked_door(), vec![lock_picker])
+            .begin(&mut dice)
+            .unwrap();
+        let (state, events) = state
+            .apply(
+                &Action::Move {
+                    to: "b".to_string(),
+                },
+                &mut dice,
+            )
+            .unwrap();
+        assert!(!events
+            .iter()
+            .any(|event| matches!(event, CombatEvent::MoveChecked { .. })));
+        assert_eq!(state.combatant(PC).unwrap().zone, "b");
+    }
+
+    fn prone(mut combatant: Combatant) -> Combatant {
+        combatant.prone = true;
+        combatant
+    }
+
+    fn check_of(events: &[CombatEvent]) -> Option<CheckResult> {
+        events.iter().find_map(|event| match event {
+            CombatEvent::Attacked { check, .. } | CombatEvent::MoveChecked { check, .. } => {
+                Some(*check)
+            }
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn lying_prone_makes_attacks_and_moves_harder() {
+        let mut dice = LoadedDice::new(&[90]);
+        let agile = CharacterSkills::new(&[SkillType::Agility(60)]);
+        let state = begin(
+            vec![
+                prone(fighter(PC, Side::Players, 9).with_skills(agile)),
+                fighter(DC, Side::Director, 1),
+            ],
+            &mut dice,
+        );
+        let attack = Action::Attack {
+            target: DC,
+            attack: 0,
+        };
+        let (_state, events) = state.apply(&attack, &mut dice).unwrap();
+        assert_eq!(check_of(&events).unwrap().target, 50 - 20);
+
+        // Crawling off takes an Agility check even where walking does not.
+        let crawl = Action::Move {
+            to: "b".to_string(),
+        };
+        let (_state, events) = state.apply(&crawl, &mut dice).unwrap();
+        assert_eq!(check_of(&events).unwrap().target, 60 - 20);
+    }
+
+    #[test]
+    fn getting_up_takes_the_action_of_the_turn() {
+        let mut dice = LoadedDice::new(&[90]);
+        let state = begin(
+            vec![
+                prone(fighter(PC, Side::Players, 9)),
+                fighter(DC, Side::Director, 1),
+            ],
+            &mut dice,
+        );
+        let (state, events) = state.apply(&Action::GetUp, &mut dice).unwrap();
+        assert!(events.contains(&CombatEvent::GotUp { combatant: PC }));
+        assert!(!state.combatant(PC).unwrap().prone);
+        assert!(state.this_turn.has_acted());
+        assert_eq!(
+            state
+                .apply(
+                    &Action::Attack {
+                        target: DC,
+                        attack: 0,
+                    },
+                    &mut dice,
+                )
+                .unwrap_err(),
+            RulesError::AlreadyActed(PC)
+        );
+        // The move is still there.
+        let (_state, events) = state
+            .apply(
+                &Action::Move {
+                    to: "b".to_string(),
+                },
+                &mut dice,
+            )
+            .unwrap();
+        assert!(check_of(&events).is_none());
+    }
+
+    #[test]
+    fn only_someone_prone_can_get_up() {
+        let mut dice = LoadedDice::new(&[90]);
+        let state = begin(vec![fighter(PC, Side::Players, 9)], &mut dice);
+        assert_eq!(
+            state.apply(&Action::GetUp, &mut dice).unwrap_err(),
+            RulesError::NotProne(PC)
+        );
+    }
+
+    #[test]
+    fn switching_weapons_cycles_through_them_once_a_turn() {
+        let mut dice = LoadedDice::new(&[90]);
+        let armed = fighter(PC, Side::Players, 9).with_attacks(vec![
+            weapon(2, vec![AttackTag::Melee]),
+            weapon(3, vec![AttackTag::ShortRange]),
+            weapon(4, vec![]),
+        ]);
+        let mut state = begin(vec![armed], &mut dice);
+        for equipped in [1, 2, 0] {
+            let events;
+            (state, events) = state.apply(&Action::SwitchWeapon, &mut dice).unwrap();
+            assert_eq!(state.combatant(PC).unwrap().equipped, equipped);
+            assert!(events.iter().any(|event| matches!(
+                event,
+                CombatEvent::WeaponSwitched { equipped: switched, .. } if *switched == equipped
+            )));
+            assert_eq!(
+                state.apply(&Action::SwitchWeapon, &mut dice).unwrap_err(),
+                RulesError::AlreadySwitched(PC)
+            );
+            (state, _) = state.apply(&Action::EndTurn, &mut dice).unwrap();
+        }
+
+        let unarmed = begin(vec![fighter(PC, Side::Players, 9)], &mut dice);
+        assert_eq!(
+            unarmed.apply(&Action::SwitchWeapon, &mut dice).unwrap_err(),
+            RulesError::NothingToSwitch(PC)
+        );
+    }
+
+    #[test]
+    fn shots_run_out_and_a_reload_fills_the_weapon_up() {
+        let mut dice = LoadedDice::new(&[90]);
+        let pistol = weapon(3, vec![AttackTag::ShortRange, AttackTag::Reload(2)]);
+        let mut state = begin(
+            vec![
+                fighter(PC, Side::Players, 9).with_attacks(vec![pistol]),
+                fighter(DC, Side::Director, 1),
+            ],
+            &mut dice,
+        );
+        let shoot = Action::Attack {
+            target: DC,
+            attack: 0,
+        };
+        assert_eq!(
+            state
+                .apply(&Action::Reload { attack: 0 }, &mut dice)
+                .unwrap_err(),
+            RulesError::NothingToReload(0)
+        );
+        for ammo in [1, 0] {
+            let events;
+            (state, events) = state.apply(&shoot, &mut dice).unwrap();
+            assert!(events.contains(&CombatEvent::AmmoSpent {
+                combatant: PC,
+                attack: 0,
+                ammo,
+            }));
+            // Round the director character's turn back to the player.
+            (state, _) = state.apply(&Action::EndTurn, &mut dice).unwrap();
+            (state, _) = state.apply(&Action::EndTurn, &mut dice).unwrap();
+        }
+        assert_eq!(
+            state.apply(&shoot, &mut dice).unwrap_err(),
+            RulesError::OutOfAmmo(0)
+        );
+
+        let (state, events) = state
+            .apply(&Action::Reload { attack: 0 }, &mut dice)
+            .unwrap();
+        assert!(events.contains(&CombatEvent::Reloaded {
+            combatant: PC,
+            attack: 0,
+            ammo: 2,
+        }));
+        assert_eq!(state.combatant(PC).unwrap().ammo, vec![Some(2)]);
+        // Reloading is the action of the turn.
+        assert_eq!(
+            state.apply(&shoot, &mut dice).unwrap_err(),
+            RulesError::AlreadyActed(PC)
+        );
+    }
+
+    #[test]
+    fn weapons_without_shots_need_no_reloading() {
+        let mut dice = LoadedDice::new(&[90]);
+        let state = begin(vec![fighter(PC, Side::Players, 9)], &mut dice);
+        assert_eq!(
+            state
+                .apply(&Action::Reload { attack: 0 }, &mut dice)
+                .unwrap_err(),
+            RulesError::NothingToReload(0)
+        );
+    }
+
+    fn with_objectives(combatants: Vec<Combatant>, objectives: Vec<Objective>) -> CombatState {
+        let mut state = CombatState::new(&corridor(), combatants);
+        state.objectives = objectives;
+        state
+    }
+
+    #[test]
+    fn surviving_is_won_once_the_rounds_are_over() {
+        let mut dice = LoadedDice::new(&[50]);
+        let (mut state, _events) = with_objectives(
+            vec![
+                fighter(PC, Side::Players, 9),
+                fighter(DC, Side::Director, 1),
+            ],
+            vec![Objective::Survive { rounds: 2 }],
+        )
+        .begin(&mut dice)
+        .unwrap();
+        for round in 1..=3 {
+            assert_eq!(state.round(), round);
+            let expected = (round > 2).then_some(Outcome::Victory);
+            assert_eq!(state.outcome(), expected, "round {}", round);
+            (state, _) = state.apply(&Action::EndTurn, &mut dice).unwrap();
+            (state, _) = state.apply(&Action::EndTurn, &mut dice).unwrap();
+        }
+    }
+
+    #[test]
+    fn defeating_a_tagged_character_wins_and_losing_a_protected_one_loses() {
+        let boss = fighter(DC, Side::Director, 1).with_slug("boss");
+        let mut state = with_objectives(
+            vec![
+                fighter(PC, Side::Players, 9),
+                boss,
+                fighter(OTHER_DC, Side::Director, 1),
+            ],
+            vec![Objective::Defeat {
+                tag: "boss".to_string(),
+            }],
+        );
+        assert_eq!(state.outcome(), None);
+        state.combatant_mut(DC).unwrap().vitality = 0;
+        assert_eq!(state.outcome(), Some(Outcome::Victory));
+
+        let ally = fighter(OTHER_PC, Side::Players, 1).with_slug("witness");
+        let mut state = with_objectives(
+            vec![
+                fighter(PC, Side::Players, 9),
+                ally,
+                fighter(DC, Side::Director, 1),
+            ],
+            vec![Objective::Protect {
+                tag: "witness".to_string(),
+            }],
+        );
+        assert_eq!(state.outcome(), None);
+        state.combatant_mut(OTHER_PC).unwrap().vitality = 0;
+        assert_eq!(state.outcome(), Some(Outcome::Defeat));
+    }
+
+    #[test]
+    fn reaching_a_zone_takes_as_many_as_the_count_asks_for() {
+        let reach = |count| {
+            with_objectives(
+                vec![
+                    fighter(PC, Side::Players, 9),
+                    fighter(OTHER_PC, Side::Players, 8),
+                    fighter(DC, Side::Director, 1),
+                ],
+                vec![Objective::Reach {
+                    zone: "b".to_string(),
+                    count,
+                }],
+            )
+        };
+        let mut one = reach(Some(1));
+        let mut everyone = reach(None);
+        for state in [&mut one, &mut everyone] {
+            state.combatant_mut(PC).unwrap().zone = "b".to_string();
+        }
+        assert_eq!(one.outcome(), Some(Outcome::Victory));
+        assert_eq!(everyone.outcome(), None);
+        // Those who are down do not count towards everyone.
+        everyone.combatant_mut(OTHER_PC).unwrap().vitality = 0;
+        assert_eq!(everyone.outcome(), Some(Outcome::Victory));
+    }
+
+    #[test]
+    fn defeat_goes_before_victory_which_goes_before_getting_away() {
+        let mut state = with_objectives(
+            vec![
+                fighter(PC, Side::Players, 9),
+                fighter(OTHER_PC, Side::Players, 8).with_slug("witness"),
+                fighter(DC, Side::Director, 1),
+            ],
+            vec![
+                Objective::Escape {
+                    zone: "a".to_string(),
+                },
+                Objective::Protect {
+                    tag: "witness".to_string(),
+                },
+            ],
+        );
+        assert_eq!(state.outcome(), Some(Outcome::Fled));
+        state.combatant_mut(DC).unwrap().vitality = 0;
+        assert_eq!(state.outcome(), Some(Outcome::Victory));
+        state.combatant_mut(OTHER_PC).unwrap().vitality = 0;
+        assert_eq!(state.outcome(), Some(Outcome::Defeat));
+    }
+}
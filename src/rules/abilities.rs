@@ -0,0 +1,98 @@
+//! What the abilities on a character sheet do in play.
+//!
+//! Every [`AbilityType`] declares its [`AbilityHooks`], the points where it
+//! changes checks and actions. Abilities without hooks only colour the
+//! character for now.
+
+use crate::characters::{AbilityType, SkillKey};
+
+/// How much an ability changes the rules, nothing by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AbilityHooks {
+    /// Extra damage done with bare hands.
+    pub unarmed_damage: u8,
+    /// Zone moves gated by a check of this skill need no check.
+    pub bypass_check: Option<SkillKey>,
+    /// Acts in a surprise round without having to notice anything first.
+    pub acts_when_surprised: bool,
+}
+
+/// The hooks of an ability.
+pub fn hooks(ability: &AbilityType) -> AbilityHooks {
+    match ability {
+        AbilityType::JudoBlackBelt => AbilityHooks {
+            unarmed_damage: 2,
+            ..Default::default()
+        },
+        AbilityType::LockPicker => AbilityHooks {
+            bypass_check: Some(SkillKey::Security),
+            ..Default::default()
+        },
+        AbilityType::SixthSence => AbilityHooks {
+            acts_when_surprised: true,
+            ..Default::default()
+        },
+        AbilityType::BornBehindTheWheel
+        | AbilityType::BeenEverywhere
+        | AbilityType::PressCredentials
+        | AbilityType::Peerage
+        | AbilityType::Bushman
+        | AbilityType::Pilot
+        | AbilityType::MilitaryRank
+        | AbilityType::FighterPilot
+        | AbilityType::TechWiz
+        | AbilityType::Young
+        | AbilityType::MadInventor => AbilityHooks::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::characters::AbilityType;
+    use crate::dice::LoadedDice;
+    use crate::rules::tests::{begin, corridor, fighter};
+    use crate::rules::{Action, CombatEvent, CombatState, CombatantId, Side};
+
+    const PC: CombatantId = CombatantId(1);
+    const DC: CombatantId = CombatantId(2);
+
+    #[test]
+    fn a_judo_black_belt_hits_harder_with_bare_hands() {
+        let mut dice = LoadedDice::new(&[21]);
+        let judoka = fighter(PC, Side::Players, 9).with_abilities(vec![AbilityType::JudoBlackBelt]);
+        let state = begin(vec![judoka, fighter(DC, Side::Director, 1)], &mut dice);
+        let (_state, events) = state
+            .apply(
+                &Action::Attack {
+                    target: DC,
+                    attack: 0,
+                },
+                &mut dice,
+            )
+            .unwrap();
+        assert!(events.contains(&CombatEvent::Damaged {
+            combatant: DC,
+            damage: 2 + 2,
+            vitality: 10 - 4,
+        }));
+    }
+
+    #[test]
+    fn a_sixth_sense_acts_in_a_surprise_round_without_a_check() {
+        // Would miss the default Alertness.
+        let mut dice = LoadedDice::new(&[72]);
+        let mut state = CombatState::new(
+            &corridor(),
+            vec![
+                fighter(PC, Side::Players, 9).with_abilities(vec![AbilityType::SixthSence]),
+                fighter(DC, Side::Director, 1),
+            ],
+        );
+        state.surprised = Some(Side::Players);
+        let (state, events) = state.begin(&mut dice).unwrap();
+        assert!(!events
+            .iter()
+            .any(|event| matches!(event, CombatEvent::SurpriseChecked { .. })));
+        assert_eq!(state.current(), Some(PC));
+    }
+}
@@ -0,0 +1,216 @@
+//! Skill checks, rolled under the skill on a d100.
+
+use std::str::FromStr;
+
+use bevy::utils::thiserror;
+use thiserror::Error;
+
+use crate::characters::{SkillKey, SkillType, UnknownSkill};
+use crate::dice::{Dice, D100};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckOutcome {
+    CriticalSuccess,
+    Success,
+    Failure,
+    CriticalFailure,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CheckResult {
+    pub roll: D100,
+    pub target: u8,
+    pub outcome: CheckOutcome,
+}
+
+impl CheckResult {
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self.outcome,
+            CheckOutcome::Success | CheckOutcome::CriticalSuccess
+        )
+    }
+
+    pub fn is_critical(&self) -> bool {
+        matches!(
+            self.outcome,
+            CheckOutcome::CriticalSuccess | CheckOutcome::CriticalFailure
+        )
+    }
+
+    /// The same check had the dice been read the other way round.
+    pub fn flipped(&self) -> CheckResult {
+        SkillCheck::new(self.target).resolve(self.roll.flipped())
+    }
+
+    /// How well the check went, the ones die of a successful roll.
+    pub fn degree(&self) -> u8 {
+        if self.is_success() {
+            self.roll.ones()
+        } else {
+            0
+        }
+    }
+}
+
+/// A check against a skill value, with any bonus or penalty applied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SkillCheck {
+    pub skill: u8,
+    pub modifier: i16,
+}
+
+impl SkillCheck {
+    pub fn new(skill: u8) -> Self {
+        Self { skill, modifier: 0 }
+    }
+
+    pub fn for_skill(skill: &SkillType) -> Self {
+        Self::new(skill.value())
+    }
+
+    /// Add a bonus (positive) or penalty (negative) to the check.
+    pub fn with_modifier(mut self, modifier: i16) -> Self {
+        self.modifier += modifier;
+        self
+    }
+
+    /// The value the roll has to come in under.
+    pub fn target(&self) -> u8 {
+        (self.skill as i16 + self.modifier).clamp(0, 100) as u8
+    }
+
+    pub fn roll(&self, dice: &mut impl Dice) -> CheckResult {
+        self.resolve(dice.d100())
+    }
+
+    pub fn resolve(&self, roll: D100) -> CheckResult {
+        let target = self.target();
+        let outcome = match (roll.value() < target, roll.is_double()) {
+            (true, true) => CheckOutcome::CriticalSuccess,
+            (true, false) => CheckOutcome::Success,
+            (false, false) => CheckOutcome::Failure,
+            (false, true) => CheckOutcome::CriticalFailure,
+        };
+        CheckResult {
+            roll,
+            target,
+            outcome,
+        }
+    }
+}
+
+/// A check written in a data file, a skill name optionally followed by a
+/// modifier, e.g. `"Agility"` or `"Security:-20"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CheckSpec {
+    pub skill: SkillKey,
+    pub modifier: i16,
+}
+
+impl CheckSpec {
+    /// The check to roll for someone with the given skill value.
+    pub fn against(&self, skill: u8) -> SkillCheck {
+        SkillCheck::new(skill).with_modifier(self.modifier)
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, Error, PartialEq)]
+pub enum CheckSpecError {
+    #[error("{0}")]
+    Skill(#[from] UnknownSkill),
+    #[error("Invalid modifier: {0}")]
+    Modifier(String),
+}
+
+impl FromStr for CheckSpec {
+    type Err = CheckSpecError;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let (skill, modifier) = match spec.split_once(':') {
+            Some((skill, modifier)) => (
+                skill,
+                modifier
+                    .trim()
+                    .trim_start_matches('+')
+                    .parse()
+                    .map_err(|_| CheckSpecError::Modifier(modifier.to_string()))?,
+            ),
+            None => (spec, 0),
+        };
+        Ok(Self {
+            skill: skill.parse()?,
+            modifier,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice::LoadedDice;
+
+    #[test]
+    fn checks_are_rolled_under_the_target() {
+        for (roll, outcome) in [
+            (1, CheckOutcome::Success),
+            (49, CheckOutcome::Success),
+            // Coming in on the target is not enough.
+            (50, CheckOutcome::Failure),
+            (51, CheckOutcome::Failure),
+            // Doubles under the target are crits, doubles over it fumbles.
+            (11, CheckOutcome::CriticalSuccess),
+            (44, CheckOutcome::CriticalSuccess),
+            (55, CheckOutcome::CriticalFailure),
+            (99, CheckOutcome::CriticalFailure),
+            (100, CheckOutcome::CriticalFailure),
+        ] {
+            let mut dice = LoadedDice::new(&[roll]);
+            let check = SkillCheck::new(50).roll(&mut dice);
+            assert_eq!(check.outcome, outcome, "roll {}", roll);
+            assert_eq!(check.target, 50);
+        }
+    }
+
+    #[test]
+    fn modifiers_move_the_target_within_bounds() {
+        assert_eq!(SkillCheck::new(50).with_modifier(-20).target(), 30);
+        assert_eq!(SkillCheck::new(90).with_modifier(30).target(), 100);
+        assert_eq!(SkillCheck::new(10).with_modifier(-20).target(), 0);
+        let check = SkillCheck::new(50).with_modifier(-20).resolve(D100(35));
+        assert_eq!(check.outcome, CheckOutcome::Failure);
+    }
+
+    #[test]
+    fn check_specs_parse_a_skill_and_a_modifier() {
+        for (spec, skill, modifier) in [
+            ("Agility", SkillKey::Agility, 0),
+            ("agility", SkillKey::Agility, 0),
+            ("Security:-20", SkillKey::Security, -20),
+            ("Sneak:+10", SkillKey::Sneak, 10),
+        ] {
+            assert_eq!(
+                spec.parse::<CheckSpec>(),
+                Ok(CheckSpec { skill, modifier }),
+                "{}",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn check_specs_reject_unknown_skills_and_bad_modifiers() {
+        assert!(matches!(
+            "Juggling".parse::<CheckSpec>(),
+            Err(CheckSpecError::Skill(_))
+        ));
+        for spec in ["Security:", "Security:hard", "Security:-20%"] {
+            assert!(
+                matches!(spec.parse::<CheckSpec>(), Err(CheckSpecError::Modifier(_))),
+                "{}",
+                spec
+            );
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! When the complications on a character sheet get in the way.
+//!
+//! Like abilities, every [`ComplicationType`] declares its
+//! [`ComplicationHooks`]. A complication that fires earns its player
+//! [story points](crate::story::StoryPoints) in return.
+
+use crate::characters::ComplicationType;
+
+/// How much a complication changes the rules, nothing by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ComplicationHooks {
+    /// Modifier to the Alertness check for reacting in a surprise round.
+    pub surprise_alertness: i16,
+}
+
+/// The hooks of a complication.
+pub fn hooks(complication: &ComplicationType) -> ComplicationHooks {
+    match complication {
+        ComplicationType::Sleepy => ComplicationHooks {
+            surprise_alertness: -20,
+        },
+        ComplicationType::Overconfident
+        | ComplicationType::CodeOfHonour
+        | ComplicationType::BadReputation
+        | ComplicationType::Honest
+        | ComplicationType::Drunkard
+        | ComplicationType::Patriot
+        | ComplicationType::Underage => ComplicationHooks::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::characters::{CharacterSkills, ComplicationType, SkillType};
+    use crate::dice::LoadedDice;
+    use crate::rules::tests::{corridor, fighter};
+    use crate::rules::{CombatEvent, CombatState, CombatantId, Side};
+    use crate::story::COMPLICATION_AWARD;
+
+    const PC: CombatantId = CombatantId(1);
+    const DC: CombatantId = CombatantId(2);
+
+    #[test]
+    fn a_sleepy_character_is_slow_to_react_and_earns_a_story_point() {
+        // Under Alertness 65 but over the 45 left once asleep.
+        let mut dice = LoadedDice::new(&[52]);
+        let sleeper = fighter(PC, Side::Players, 9)
+            .with_skills(CharacterSkills::new(&[SkillType::Alertness(65)]))
+            .with_complications(vec![ComplicationType::Sleepy]);
+        let mut state =
+            CombatState::new(&corridor(), vec![sleeper, fighter(DC, Side::Director, 1)]);
+        state.surprised = Some(Side::Players);
+        let (state, events) = state.begin(&mut dice).unwrap();
+        assert!(events.contains(&CombatEvent::ComplicationTriggered {
+            combatant: PC,
+            complication: ComplicationType::Sleepy,
+        }));
+        let check = events
+            .iter()
+            .find_map(|event| match event {
+                CombatEvent::SurpriseChecked { combatant, check } if *combatant == PC => {
+                    Some(*check)
+                }
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(check.target, 45);
+        assert!(!check.is_success());
+        assert_eq!(
+            state.combatant(PC).unwrap().story_points,
+            COMPLICATION_AWARD
+        );
+        assert_eq!(state.current(), Some(DC));
+    }
+}
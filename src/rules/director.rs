@@ -0,0 +1,237 @@
+//! How director characters play their turns.
+//!
+//! Each director character gets a [`Behaviour`] picked from its [`DCTag`]s,
+//! which is asked for one [`Action`] at a time until it ends the turn.
+
+use crate::characters::DCTag;
+
+use super::checks::CheckResult;
+use super::flips::Flipper;
+use super::{Action, CombatState, Combatant, CombatantId, Side, TurnSoFar};
+
+pub trait Behaviour {
+    /// The next thing to do, [`Action::EndTurn`] when done.
+    fn next_action(&self, state: &CombatState, actor: CombatantId, turn: &TurnSoFar) -> Action;
+}
+
+/// Pick the behaviour profile for a director character from its tags.
+pub fn behaviour_for(tags: &[DCTag]) -> Box<dyn Behaviour + Send + Sync> {
+    if tags.iter().any(|tag| matches!(tag, DCTag::Lieutenant)) {
+        Box::new(Lieutenant)
+    } else {
+        Box::new(Mook)
+    }
+}
+
+/// Go for the closest opponent and hit it with the first attack at hand.
+pub struct Mook;
+
+impl Behaviour for Mook {
+    fn next_action(&self, state: &CombatState, actor: CombatantId, turn: &TurnSoFar) -> Action {
+        let Some(me) = state.combatant(actor) else {
+            return Action::EndTurn;
+        };
+        let attack = (0..me.attacks.len())
+            .find(|attack| me.is_loaded(*attack))
+            .unwrap_or(0);
+        attack_or_approach(state, me, turn, attack, |targets| {
+            closest(state, me, targets)
+        })
+    }
+}
+
+/// Pick on the most hurt opponent in reach with the hardest hitting loaded
+/// attack, otherwise close in on the nearest one.
+pub struct Lieutenant;
+
+impl Behaviour for Lieutenant {
+    fn next_action(&self, state: &CombatState, actor: CombatantId, turn: &TurnSoFar) -> Action {
+        let Some(me) = state.combatant(actor) else {
+            return Action::EndTurn;
+        };
+        let attack = me
+            .attacks
+            .iter()
+            .enumerate()
+            .max_by_key(|(index, attack)| (me.is_loaded(*index), attack.damage, usize::MAX - index))
+            .map(|(index, _attack)| index)
+            .unwrap_or(0);
+        attack_or_approach(state, me, turn, attack, |targets| {
+            targets
+                .iter()
+                .filter_map(|id| state.combatant(*id))
+                .min_by_key(|target| target.vitality)
+                .map(|target| target.id)
+        })
+    }
+}
+
+/// Director characters spend a flip whenever it turns a failed check into a
+/// success, and leave player characters' rolls alone.
+pub struct DirectorFlips;
+
+impl Flipper for DirectorFlips {
+    fn flip(&mut self, state: &CombatState, roller: CombatantId, check: &CheckResult) -> bool {
+        let director = state
+            .combatant(roller)
+            .is_some_and(|combatant| combatant.side == Side::Director);
+        director && !check.is_success() && check.flipped().is_success()
+    }
+}
+
+fn attack_or_approach(
+    state: &CombatState,
+    me: &Combatant,
+    turn: &TurnSoFar,
+    attack: usize,
+    pick: impl Fn(&[CombatantId]) -> Option<CombatantId>,
+) -> Action {
+    if me.attacks.is_empty() {
+        return Action::EndTurn;
+    }
+    // Lying down is only worth it with someone to hit right away.
+    if me.prone && turn.can_get_up() && pick(&state.targets(me.id, attack)).is_none() {
+        return Action::GetUp;
+    }
+    let loaded = me.is_loaded(attack);
+    if !loaded && turn.can_reload() && me.can_reload(attack) {
+        return Action::Reload { attack };
+    }
+    if loaded && turn.can_attack(me.attacks_per_turn()) {
+        if let Some(target) = pick(&state.targets(me.id, attack)) {
+            return Action::Attack { target, attack };
+        }
+    }
+    if turn.can_move() && turn.attacks == 0 {
+        if let Some(next) = opponents(state, me)
+            .into_iter()
+            .filter_map(|target| state.path(&me.zone, &target.zone))
+            .filter(|path| !path.is_empty())
+            .min_by_key(|path| path.len())
+            .and_then(|path| path.into_iter().next())
+        {
+            return Action::Move { to: next };
+        }
+    }
+    Action::EndTurn
+}
+
+fn opponents<'a>(state: &'a CombatState, me: &Combatant) -> Vec<&'a Combatant> {
+    state
+        .combatants
+        .iter()
+        .filter(|other| other.side != me.side && !other.is_incapacitated())
+        .collect()
+}
+
+fn closest(state: &CombatState, me: &Combatant, targets: &[CombatantId]) -> Option<CombatantId> {
+    targets
+        .iter()
+        .filter_map(|id| state.combatant(*id))
+        .min_by_key(|target| {
+            state
+                .path(&me.zone, &target.zone)
+                .map(|path| path.len())
+                .unwrap_or(usize::MAX)
+        })
+        .map(|target| target.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::characters::{Attack, AttackTag};
+    use crate::dice::LoadedDice;
+    use crate::rules::tests::{begin, fighter};
+
+    const PC: CombatantId = CombatantId(1);
+    const DC: CombatantId = CombatantId(2);
+
+    /// The director character to act first, with the player character in
+    /// the zone given.
+    fn facing(dc: Combatant, pc_zone: &str, dice: &mut LoadedDice) -> CombatState {
+        let mut pc = fighter(PC, Side::Players, 1);
+        pc.zone = pc_zone.to_string();
+        begin(vec![dc, pc], dice)
+    }
+
+    fn next_action(state: &CombatState, behaviour: &dyn Behaviour) -> Action {
+        behaviour.next_action(state, DC, &state.this_turn)
+    }
+
+    #[test]
+    fn a_mook_attacks_a_target_in_reach() {
+        let mut dice = LoadedDice::new(&[50]);
+        let state = facing(fighter(DC, Side::Director, 9), "a", &mut dice);
+        assert_eq!(
+            next_action(&state, &Mook),
+            Action::Attack {
+                target: PC,
+                attack: 0
+            }
+        );
+    }
+
+    #[test]
+    fn a_mook_closes_in_when_nobody_is_in_reach() {
+        let mut dice = LoadedDice::new(&[50]);
+        let state = facing(fighter(DC, Side::Director, 9), "c", &mut dice);
+        assert_eq!(
+            next_action(&state, &Mook),
+            Action::Move {
+                to: "b".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn an_empty_weapon_is_reloaded() {
+        let mut dice = LoadedDice::new(&[50]);
+        let pistol = Attack {
+            name: "Pistol".to_string(),
+            skill: 50,
+            damage: 3,
+            tags: vec![AttackTag::ShortRange, AttackTag::Reload(6)],
+        };
+        let mut dc = fighter(DC, Side::Director, 9).with_attacks(vec![pistol]);
+        dc.ammo = vec![Some(0)];
+        let state = facing(dc, "a", &mut dice);
+        assert_eq!(next_action(&state, &Mook), Action::Reload { attack: 0 });
+    }
+
+    #[test]
+    fn a_prone_mook_gets_up_with_nobody_to_hit() {
+        let mut dice = LoadedDice::new(&[50]);
+        let mut dc = fighter(DC, Side::Director, 9);
+        dc.prone = true;
+        let state = facing(dc.clone(), "c", &mut dice);
+        assert_eq!(next_action(&state, &Mook), Action::GetUp);
+
+        // Someone to hit right away is worth staying down for.
+        let state = facing(dc, "a", &mut dice);
+        assert_eq!(
+            next_action(&state, &Mook),
+            Action::Attack {
+                target: PC,
+                attack: 0
+            }
+        );
+    }
+
+    #[test]
+    fn a_lieutenant_uses_every_attack_of_the_turn() {
+        // Misses all round, so the target stays up.
+        let mut dice = LoadedDice::new(&[90]);
+        let dc = fighter(DC, Side::Director, 9).with_tags(vec![DCTag::MultipleAttacks(2)]);
+        let mut state = facing(dc, "a", &mut dice);
+        let attack = Action::Attack {
+            target: PC,
+            attack: 0,
+        };
+        for _ in 0..2 {
+            assert_eq!(next_action(&state, &Lieutenant), attack);
+            (state, _) = state.apply(&attack, &mut dice).unwrap();
+        }
+        assert_eq!(next_action(&state, &Lieutenant), Action::EndTurn);
+    }
+}
@@ -0,0 +1,263 @@
+//! Timed status effects on combatants, such as being paralysed.
+//!
+//! An effect lasts a number of rounds, counted down each time a turn of the
+//! combatant carrying it starts. What an effect does is given by its hooks,
+//! run when it is applied, when a turn of its bearer starts and when it runs
+//! out.
+
+use crate::characters::SkillKey;
+use crate::dice::Dice;
+
+use super::checks::SkillCheck;
+use super::flips::Flipper;
+use super::{CombatEvent, CombatState, CombatantId, RulesError};
+
+/// How many rounds a paralytic hit keeps the target paralysed.
+pub const PARALYSED_ROUNDS: u32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StatusKind {
+    /// Loses the turn unless a Willpower or Endurance check is passed.
+    Paralysed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    /// Turns of the bearer left before the effect wears off.
+    pub rounds: u32,
+}
+
+impl StatusEffect {
+    pub fn new(kind: StatusKind, rounds: u32) -> Self {
+        Self { kind, rounds }
+    }
+}
+
+impl CombatState {
+    /// Put an effect on a combatant. Landing on someone already under it, it
+    /// keeps whichever of the two lasts longer.
+    pub(super) fn add_status(
+        &mut self,
+        combatant: CombatantId,
+        effect: StatusEffect,
+        events: &mut Vec<CombatEvent>,
+    ) -> Result<(), RulesError> {
+        let bearer = self.combatant_mut(combatant)?;
+        if bearer.is_incapacitated() || effect.rounds == 0 {
+            return Ok(());
+        }
+        let rounds = match bearer
+            .statuses
+            .iter_mut()
+            .find(|status| status.kind == effect.kind)
+        {
+            Some(status) => {
+                status.rounds = status.rounds.max(effect.rounds);
+                status.rounds
+            }
+            None => {
+                bearer.statuses.push(effect);
+                effect.rounds
+            }
+        };
+        events.push(CombatEvent::StatusApplied {
+            combatant,
+            status: effect.kind,
+            rounds,
+        });
+        self.on_apply(combatant, effect.kind, events)
+    }
+
+    /// Run the effects on a combatant whose turn starts and count them down,
+    /// giving back whether the turn is lost.
+    pub(super) fn start_turn_effects(
+        &mut self,
+        combatant: CombatantId,
+        dice: &mut impl Dice,
+        flipper: &mut impl Flipper,
+        events: &mut Vec<CombatEvent>,
+    ) -> Result<bool, RulesError> {
+        let statuses = self.combatant_mut(combatant)?.statuses.clone();
+        let mut skip_turn = false;
+        for status in statuses.iter() {
+            skip_turn |= self.on_turn_start(combatant, status.kind, dice, flipper, events)?;
+        }
+        let bearer = self.combatant_mut(combatant)?;
+        for status in bearer.statuses.iter_mut() {
+            status.rounds = status.rounds.saturating_sub(1);
+        }
+        let (expired, left): (Vec<StatusEffect>, Vec<StatusEffect>) = bearer
+            .statuses
+            .drain(..)
+            .partition(|status| status.rounds == 0);
+        bearer.statuses = left;
+        for status in expired {
+            events.push(CombatEvent::StatusExpired {
+                combatant,
+                status: status.kind,
+            });
+            self.on_expire(combatant, status.kind, events)?;
+        }
+        Ok(skip_turn)
+    }
+
+    fn on_apply(
+        &mut self,
+        _combatant: CombatantId,
+        kind: StatusKind,
+        _events: &mut Vec<CombatEvent>,
+    ) -> Result<(), RulesError> {
+        match kind {
+            StatusKind::Paralysed => Ok(()),
+        }
+    }
+
+    /// Returns whether the effect costs the bearer the turn.
+    fn on_turn_start(
+        &mut self,
+        combatant: CombatantId,
+        kind: StatusKind,
+        dice: &mut impl Dice,
+        flipper: &mut impl Flipper,
+        events: &mut Vec<CombatEvent>,
+    ) -> Result<bool, RulesError> {
+        match kind {
+            StatusKind::Paralysed => {
+                // Fighting it off takes will or toughness, whichever is best.
+                let bearer = self.combatant_mut(combatant)?;
+                let skill = [SkillKey::Willpower, SkillKey::Endurance]
+                    .into_iter()
+                    .max_by_key(|skill| (bearer.skill(*skill), *skill == SkillKey::Willpower))
+                    .unwrap_or(SkillKey::Willpower);
+                let skill_check = SkillCheck::new(bearer.skill(skill));
+                let check = self.roll_check(combatant, skill_check, dice, flipper, events)?;
+                events.push(CombatEvent::StatusChecked {
+                    combatant,
+                    status: kind,
+                    skill,
+                    check,
+                });
+                Ok(!check.is_success())
+            }
+        }
+    }
+
+    fn on_expire(
+        &mut self,
+        _combatant: CombatantId,
+        kind: StatusKind,
+        _events: &mut Vec<CombatEvent>,
+    ) -> Result<(), RulesError> {
+        match kind {
+            StatusKind::Paralysed => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::ron;
+
+    use super::*;
+    use crate::dice::LoadedDice;
+    use crate::rules::{Action, Combatant, Side};
+
+    const PC: CombatantId = CombatantId(1);
+    const DC: CombatantId = CombatantId(2);
+
+    /// The player character to act first, then a paralysed director
+    /// character.
+    fn paralysed_director(rounds: u32) -> CombatState {
+        let map = ron::from_str(r#"(bitmap: "none", zones: [], start_positions: [])"#).unwrap();
+        let mut state = CombatState::new(
+            &map,
+            vec![
+                Combatant::new(PC, Side::Players, 9, 10, "a"),
+                Combatant::new(DC, Side::Director, 5, 10, "a"),
+            ],
+        );
+        let mut events = Vec::new();
+        state
+            .add_status(
+                DC,
+                StatusEffect::new(StatusKind::Paralysed, rounds),
+                &mut events,
+            )
+            .unwrap();
+        state
+    }
+
+    fn statuses(state: &CombatState, combatant: CombatantId) -> Vec<StatusEffect> {
+        state.combatant(combatant).unwrap().statuses.clone()
+    }
+
+    #[test]
+    fn an_effect_landing_again_keeps_the_longer_of_the_two() {
+        let mut state = paralysed_director(2);
+        let mut events = Vec::new();
+        let paralysed = |rounds| StatusEffect::new(StatusKind::Paralysed, rounds);
+
+        state.add_status(DC, paralysed(1), &mut events).unwrap();
+        assert_eq!(statuses(&state, DC), vec![paralysed(2)]);
+        state.add_status(DC, paralysed(3), &mut events).unwrap();
+        assert_eq!(statuses(&state, DC), vec![paralysed(3)]);
+        assert_eq!(
+            events,
+            vec![
+                CombatEvent::StatusApplied {
+                    combatant: DC,
+                    status: StatusKind::Paralysed,
+                    rounds: 2,
+                },
+                CombatEvent::StatusApplied {
+                    combatant: DC,
+                    status: StatusKind::Paralysed,
+                    rounds: 3,
+                },
+            ]
+        );
+
+        // Nothing lands on someone who is already down.
+        state.combatant_mut(PC).unwrap().vitality = 0;
+        state.add_status(PC, paralysed(2), &mut events).unwrap();
+        assert!(statuses(&state, PC).is_empty());
+    }
+
+    #[test]
+    fn a_paralysed_combatant_who_fails_the_check_loses_the_turn() {
+        // Over the default Willpower and Endurance.
+        let mut dice = LoadedDice::new(&[72]);
+        let (state, _events) = paralysed_director(2).begin(&mut dice).unwrap();
+        let (state, events) = state.apply(&Action::EndTurn, &mut dice).unwrap();
+        assert!(events.contains(&CombatEvent::TurnSkipped { combatant: DC }));
+        assert_eq!(state.current(), Some(PC));
+        assert_eq!(
+            statuses(&state, DC),
+            vec![StatusEffect::new(StatusKind::Paralysed, 1)]
+        );
+    }
+
+    #[test]
+    fn a_paralysed_combatant_who_passes_the_check_shakes_it_off_for_the_turn() {
+        let mut dice = LoadedDice::new(&[12]);
+        let (state, _events) = paralysed_director(2).begin(&mut dice).unwrap();
+        let (state, events) = state.apply(&Action::EndTurn, &mut dice).unwrap();
+        assert!(!events.contains(&CombatEvent::TurnSkipped { combatant: DC }));
+        assert_eq!(state.current(), Some(DC));
+    }
+
+    #[test]
+    fn an_effect_expires_once_its_rounds_are_counted_down() {
+        let mut dice = LoadedDice::new(&[72]);
+        let (state, _events) = paralysed_director(1).begin(&mut dice).unwrap();
+        let (state, events) = state.apply(&Action::EndTurn, &mut dice).unwrap();
+        // The last round still costs the turn.
+        assert!(events.contains(&CombatEvent::TurnSkipped { combatant: DC }));
+        assert!(events.contains(&CombatEvent::StatusExpired {
+            combatant: DC,
+            status: StatusKind::Paralysed,
+        }));
+        assert!(statuses(&state, DC).is_empty());
+    }
+}
@@ -0,0 +1,319 @@
+//! Flipping a check, reading the d100 with its tens and ones swapped.
+//!
+//! Director characters with [`DCTag::Flips`](crate::characters::DCTag::Flips)
+//! each carry their own flips, while the player characters share a pool. A
+//! [`Flipper`] decides whether a roll is worth one, and whether a player
+//! character spends one of their story points on it once the flips are passed
+//! on.
+
+use crate::dice::Dice;
+
+use super::checks::{CheckResult, SkillCheck};
+use super::{CombatEvent, CombatState, CombatantId, RulesError, Side};
+
+/// The flips the player characters share at the start of a combat.
+pub const PLAYER_FLIPS: u8 = 2;
+
+/// What bringing signature gear into play adds to the check.
+pub const SIGNATURE_GEAR_BONUS: i16 = 20;
+
+/// Story points earned each time a complication fires.
+pub const COMPLICATION_AWARD: u8 = 1;
+
+/// Story points earned each time a plot hook comes up.
+pub const PLOT_HOOK_AWARD: u8 = 1;
+
+/// What a story point is spent on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StorySpend {
+    /// Flip the check without touching the flips.
+    Flip,
+    /// Roll the check again and keep the new roll.
+    Reroll,
+    /// Bring signature gear into play, adding its bonus to the check.
+    SignatureGear,
+}
+
+impl StorySpend {
+    /// The check as it comes out once the story point is spent on it.
+    pub fn apply(
+        &self,
+        skill_check: SkillCheck,
+        check: CheckResult,
+        dice: &mut impl Dice,
+    ) -> CheckResult {
+        match self {
+            StorySpend::Flip => check.flipped(),
+            StorySpend::Reroll => skill_check.roll(dice),
+            StorySpend::SignatureGear => skill_check
+                .with_modifier(SIGNATURE_GEAR_BONUS)
+                .resolve(check.roll),
+        }
+    }
+}
+
+pub trait Flipper {
+    /// Whether the roller spends a flip on the check they just rolled.
+    fn flip(&mut self, state: &CombatState, roller: CombatantId, check: &CheckResult) -> bool;
+
+    /// Which of the `spends` the roller uses a story point on, if any, after
+    /// passing on a flip.
+    fn spend_story_point(
+        &mut self,
+        _state: &CombatState,
+        _roller: CombatantId,
+        _check: &CheckResult,
+        _spends: &[StorySpend],
+    ) -> Option<StorySpend> {
+        None
+    }
+}
+
+/// Never flips, for when nobody gets to decide.
+pub struct NoFlips;
+
+impl Flipper for NoFlips {
+    fn flip(&mut self, _state: &CombatState, _roller: CombatantId, _check: &CheckResult) -> bool {
+        false
+    }
+}
+
+impl CombatState {
+    /// The flips the combatant can still spend, from the player pool for
+    /// player characters.
+    pub fn flips_left(&self, id: CombatantId) -> u8 {
+        match self.combatant(id) {
+            Some(combatant) if combatant.side == Side::Players => self.player_flips,
+            Some(combatant) => combatant.flips,
+            None => 0,
+        }
+    }
+
+    /// Roll a check for a combatant, who may spend a flip or a story point on
+    /// it.
+    pub(super) fn roll_check(
+        &mut self,
+        roller: CombatantId,
+        skill_check: SkillCheck,
+        dice: &mut impl Dice,
+        flipper: &mut impl Flipper,
+        events: &mut Vec<CombatEvent>,
+    ) -> Result<CheckResult, RulesError> {
+        self.roll_gear_check(roller, skill_check, false, dice, flipper, events)
+    }
+
+    /// Roll a check made with some gear, which a story point can bring into
+    /// play if it is the roller's signature gear.
+    pub(super) fn roll_gear_check(
+        &mut self,
+        roller: CombatantId,
+        skill_check: SkillCheck,
+        signature_gear: bool,
+        dice: &mut impl Dice,
+        flipper: &mut impl Flipper,
+        events: &mut Vec<CombatEvent>,
+    ) -> Result<CheckResult, RulesError> {
+        let check = skill_check.roll(dice);
+        let flipped = check.flipped();
+        let can_flip = flipped.roll != check.roll;
+        if !can_flip || self.flips_left(roller) == 0 || !flipper.flip(self, roller, &check) {
+            return self.spend_story_point(
+                roller,
+                skill_check,
+                check,
+                signature_gear,
+                dice,
+                flipper,
+                events,
+            );
+        }
+        let flips = if self.combatant_mut(roller)?.side == Side::Players {
+            &mut self.player_flips
+        } else {
+            &mut self.combatant_mut(roller)?.flips
+        };
+        *flips -= 1;
+        events.push(CombatEvent::Flipped {
+            combatant: roller,
+            from: check.roll,
+            to: flipped.roll,
+            flips_left: *flips,
+        });
+        Ok(flipped)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn spend_story_point(
+        &mut self,
+        roller: CombatantId,
+        skill_check: SkillCheck,
+        check: CheckResult,
+        signature_gear: bool,
+        dice: &mut impl Dice,
+        flipper: &mut impl Flipper,
+        events: &mut Vec<CombatEvent>,
+    ) -> Result<CheckResult, RulesError> {
+        if self.combatant_mut(roller)?.story_points == 0 {
+            return Ok(check);
+        }
+        let spends: Vec<StorySpend> = [
+            (check.flipped().roll != check.roll).then_some(StorySpend::Flip),
+            Some(StorySpend::Reroll),
+            signature_gear.then_some(StorySpend::SignatureGear),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let Some(spend) = flipper
+            .spend_story_point(self, roller, &check, &spends)
+            .filter(|spend| spends.contains(spend))
+        else {
+            return Ok(check);
+        };
+        let result = spend.apply(skill_check, check, dice);
+        let combatant = self.combatant_mut(roller)?;
+        combatant.story_points -= 1;
+        events.push(CombatEvent::StoryPointSpent {
+            combatant: roller,
+            spend,
+            from: check,
+            to: result,
+            story_points_left: combatant.story_points,
+        });
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::ron;
+
+    use super::*;
+    use crate::dice::{LoadedDice, D100};
+    use crate::rules::Combatant;
+
+    const PC: CombatantId = CombatantId(1);
+    const OTHER_PC: CombatantId = CombatantId(2);
+
+    /// Flips whenever it gets the chance.
+    struct AlwaysFlip;
+
+    impl Flipper for AlwaysFlip {
+        fn flip(
+            &mut self,
+            _state: &CombatState,
+            _roller: CombatantId,
+            _check: &CheckResult,
+        ) -> bool {
+            true
+        }
+    }
+
+    fn players() -> CombatState {
+        let map = ron::from_str(r#"(bitmap: "none", zones: [], start_positions: [])"#).unwrap();
+        CombatState::new(
+            &map,
+            vec![
+                Combatant::new(PC, Side::Players, 5, 10, "a"),
+                Combatant::new(OTHER_PC, Side::Players, 5, 10, "a"),
+            ],
+        )
+    }
+
+    #[test]
+    fn a_flip_turns_a_failure_into_a_success() {
+        let mut state = players();
+        let mut events = Vec::new();
+        let mut dice = LoadedDice::new(&[72]);
+        let check = state
+            .roll_check(
+                PC,
+                SkillCheck::new(50),
+                &mut dice,
+                &mut AlwaysFlip,
+                &mut events,
+            )
+            .unwrap();
+        assert_eq!((check.roll, check.is_success()), (D100(27), true));
+        assert_eq!(state.flips_left(PC), PLAYER_FLIPS - 1);
+        assert_eq!(
+            events,
+            vec![CombatEvent::Flipped {
+                combatant: PC,
+                from: D100(72),
+                to: D100(27),
+                flips_left: PLAYER_FLIPS - 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn the_player_characters_share_their_flips_until_they_run_out() {
+        let mut state = players();
+        let mut events = Vec::new();
+        let mut dice = LoadedDice::new(&[72]);
+        for roller in [PC, OTHER_PC]
+            .into_iter()
+            .cycle()
+            .take(PLAYER_FLIPS as usize)
+        {
+            let check = state
+                .roll_check(
+                    roller,
+                    SkillCheck::new(50),
+                    &mut dice,
+                    &mut AlwaysFlip,
+                    &mut events,
+                )
+                .unwrap();
+            assert!(check.is_success());
+        }
+        assert_eq!(state.flips_left(OTHER_PC), 0);
+
+        events.clear();
+        let check = state
+            .roll_check(
+                PC,
+                SkillCheck::new(50),
+                &mut dice,
+                &mut AlwaysFlip,
+                &mut events,
+            )
+            .unwrap();
+        assert_eq!((check.roll, check.is_success()), (D100(72), false));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn story_points_flip_reroll_or_bring_in_signature_gear() {
+        let skill_check = SkillCheck::new(50);
+        let check = skill_check.resolve(D100(62));
+        let mut dice = LoadedDice::new(&[81]);
+
+        let flipped = StorySpend::Flip.apply(skill_check, check, &mut dice);
+        assert_eq!((flipped.roll, flipped.is_success()), (D100(26), true));
+
+        let rerolled = StorySpend::Reroll.apply(skill_check, check, &mut dice);
+        assert_eq!((rerolled.roll, rerolled.is_success()), (D100(81), false));
+
+        // The same roll, against a target raised by the bonus.
+        let geared = StorySpend::SignatureGear.apply(skill_check, check, &mut dice);
+        assert_eq!(geared.roll, D100(62));
+        assert_eq!(geared.target, 50 + SIGNATURE_GEAR_BONUS as u8);
+        assert!(geared.is_success());
+    }
+
+    #[test]
+    fn a_surprised_player_character_can_flip_the_opening_alertness_check() {
+        let mut state = players();
+        state.surprised = Some(Side::Players);
+        // A miss on the default Alertness, which a flip turns into 13.
+        let mut dice = LoadedDice::new(&[31]);
+        let (state, events) = state.begin_with_flips(&mut dice, &mut AlwaysFlip).unwrap();
+        assert!(events.iter().any(|event| matches!(
+            event,
+            CombatEvent::SurpriseChecked { combatant: PC, check } if check.is_success()
+        )));
+        assert_eq!(state.current(), Some(PC));
+    }
+}
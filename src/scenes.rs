@@ -0,0 +1,112 @@
+use bevy::asset::ron;
+use bevy::prelude::*;
+
+use crate::asset_loader::CombatAsset;
+use crate::campaign::{Campaign, Scene};
+use crate::rules::Outcome;
+use crate::states::GameState;
+use crate::story::{self, StoryPoints};
+
+pub struct Scenes;
+
+impl Plugin for Scenes {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NextScene>()
+            .init_resource::<StoryPoints>()
+            .add_systems(
+                OnEnter(GameState::SceneChange),
+                (save_story_points, change_scene),
+            );
+    }
+}
+
+/// The scene to go to on the next [`GameState::SceneChange`], the end if none.
+#[derive(Resource, Debug, Default)]
+pub struct NextScene(pub Option<String>);
+
+/// The campaign scene being played.
+#[derive(Resource, Debug, Clone)]
+pub struct CurrentScene {
+    pub id: String,
+    pub scene: Scene,
+}
+
+impl CurrentScene {
+    /// Leave the scene for the one that follows it.
+    pub fn finish(
+        &self,
+        outcome: Option<Outcome>,
+        next_scene: &mut NextScene,
+        game_state: &mut NextState<GameState>,
+    ) {
+        info!("scene {} finished: {:?}", self.id, outcome);
+        next_scene.0 = self.scene.next(outcome).map(String::from);
+        game_state.set(GameState::SceneChange);
+    }
+}
+
+fn change_scene(
+    mut commands: Commands,
+    next_scene: Res<NextScene>,
+    combat_asset: Res<CombatAsset>,
+    campaigns: Res<Assets<Campaign>>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    let scene = next_scene.0.as_ref().and_then(|id| {
+        campaigns
+            .get(&combat_asset.campaign)
+            .and_then(|campaign| campaign.scene(id))
+            .map(|scene| CurrentScene {
+                id: id.clone(),
+                scene: scene.clone(),
+            })
+    });
+    let Some(scene) = scene else {
+        game_state.set(GameState::TheEnd);
+        return;
+    };
+    info!("scene {}", scene.id);
+    game_state.set(match scene.scene {
+        Scene::Dialog { .. } => GameState::Dialog,
+        Scene::Combat { .. } => GameState::Combat,
+    });
+    commands.insert_resource(scene);
+}
+
+/// The story points saved by the last game, if there is a save.
+pub fn saved_story_points() -> Option<StoryPoints> {
+    let ron = std::fs::read_to_string(story::save_file()).ok()?;
+    ron::from_str(&ron)
+        .map_err(|error| warn!("could not read saved story points: {}", error))
+        .ok()
+}
+
+/// Save the story points whenever they changed since the last save, so the
+/// next game picks up from here.
+fn save_story_points(story_points: Res<StoryPoints>, mut last_saved: Local<Option<StoryPoints>>) {
+    // The first scene change comes straight from loading, with nothing new
+    // to save yet.
+    let Some(saved) = last_saved.as_ref() else {
+        *last_saved = Some(story_points.clone());
+        return;
+    };
+    if saved == &*story_points {
+        return;
+    }
+    let path = story::save_file();
+    let written = ron::ser::to_string_pretty(&*story_points, ron::ser::PrettyConfig::default())
+        .map_err(|error| error.to_string())
+        .and_then(|ron| {
+            path.parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::write(&path, ron))
+                .map_err(|error| error.to_string())
+        });
+    match written {
+        Ok(()) => {
+            info!("saved story points to {}", path.display());
+            *last_saved = Some(story_points.clone());
+        }
+        Err(error) => warn!("could not save story points: {}", error),
+    }
+}
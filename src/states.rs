@@ -7,6 +7,8 @@ pub enum GameState {
     AssetsLoading,
     AssetsSetup,
     AssetsFailed,
+    SceneChange,
+    Dialog,
     Combat,
     CombatTurns,
     CombatEnded,
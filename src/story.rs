@@ -0,0 +1,148 @@
+//! Story points, earned by the players when their complications get in the
+//! way or their plot hooks come up, as in the Troubleshooters rules. They are
+//! spent on checks, to flip or reroll them or to bring in signature gear.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use bevy::asset::io::file::FileAssetReader;
+use bevy::prelude::Resource;
+use serde::{Deserialize, Serialize};
+
+use crate::characters::{ComplicationType, PlotHook, SaveCharacters, SavePlayerCharacter};
+pub use crate::rules::flips::{COMPLICATION_AWARD, PLOT_HOOK_AWARD};
+
+/// Where the story points are saved between games, next to the assets
+/// folder rather than in it so the shipped characters are never shadowed.
+pub fn save_file() -> PathBuf {
+    FileAssetReader::get_base_path()
+        .join("saves")
+        .join("story_points.ron")
+}
+
+/// Whether to start over with `--new-game` on the command line, dropping the
+/// story points saved by the last game.
+#[derive(Resource, Debug, Default)]
+pub struct NewGame(pub bool);
+
+impl NewGame {
+    pub fn from_args() -> Self {
+        Self(std::env::args().any(|arg| arg == "--new-game"))
+    }
+}
+
+/// Something on a character sheet that earns story points when it comes up
+/// in play.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StoryTrigger {
+    Complication(ComplicationType),
+    PlotHook(PlotHook),
+}
+
+impl std::fmt::Display for StoryTrigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoryTrigger::Complication(complication) => write!(f, "{:?}", complication),
+            StoryTrigger::PlotHook(plot_hook) => write!(f, "{:?}", plot_hook),
+        }
+    }
+}
+
+impl StoryTrigger {
+    pub fn award(&self) -> u8 {
+        match self {
+            StoryTrigger::Complication(_) => COMPLICATION_AWARD,
+            StoryTrigger::PlotHook(_) => PLOT_HOOK_AWARD,
+        }
+    }
+
+    /// Whether the character has it, only then earning anything.
+    pub fn applies_to(&self, pc: &SavePlayerCharacter) -> bool {
+        match self {
+            StoryTrigger::Complication(complication) => pc.complications.contains(complication),
+            StoryTrigger::PlotHook(plot_hook) => pc.plot_hooks.contains(plot_hook),
+        }
+    }
+}
+
+/// The story points of each player character, by tag.
+#[derive(Resource, Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct StoryPoints(HashMap<String, u8>);
+
+impl StoryPoints {
+    /// The story points the player characters start out with.
+    pub fn from_save(save: &SaveCharacters) -> Self {
+        Self(
+            save.player_characters
+                .iter()
+                .map(|pc| (pc.tag.clone(), pc.story_points))
+                .collect(),
+        )
+    }
+
+    /// Take over the story points saved by an earlier game.
+    pub fn carry_over(&mut self, saved: &StoryPoints) {
+        for (tag, points) in saved.0.iter() {
+            self.set(tag, *points);
+        }
+    }
+
+    pub fn get(&self, tag: &str) -> u8 {
+        self.0.get(tag).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, tag: &str, points: u8) {
+        self.0.insert(tag.to_string(), points);
+    }
+
+    pub fn award(&mut self, tag: &str, points: u8) {
+        let story_points = self.0.entry(tag.to_string()).or_default();
+        *story_points = story_points.saturating_add(points);
+    }
+
+    /// Take a story point from a character, if they have one left.
+    pub fn spend(&mut self, tag: &str) -> bool {
+        match self.0.get_mut(tag) {
+            Some(story_points) if *story_points > 0 => {
+                *story_points -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn story_points_are_awarded_and_spent_one_at_a_time() {
+        let mut story_points = StoryPoints::default();
+        assert!(!story_points.spend("paul"));
+
+        story_points.award("paul", COMPLICATION_AWARD);
+        story_points.award("paul", PLOT_HOOK_AWARD);
+        assert_eq!(story_points.get("paul"), 2);
+        assert!(story_points.spend("paul"));
+        assert_eq!(story_points.get("paul"), 1);
+        assert_eq!(story_points.get("harry"), 0);
+
+        story_points.set("paul", u8::MAX);
+        story_points.award("paul", PLOT_HOOK_AWARD);
+        assert_eq!(story_points.get("paul"), u8::MAX);
+    }
+
+    #[test]
+    fn saved_story_points_replace_the_starting_ones() {
+        let mut story_points = StoryPoints::default();
+        story_points.set("paul", 1);
+        story_points.set("harry", 2);
+        let mut saved = StoryPoints::default();
+        saved.set("paul", 4);
+
+        story_points.carry_over(&saved);
+        assert_eq!(story_points.get("paul"), 4);
+        assert_eq!(story_points.get("harry"), 2);
+    }
+}